@@ -9,8 +9,14 @@ window.findMatch = function findMatch() {
     let str = document.getElementById('str').value;
     let regExp = document.getElementById('regExp').value;
 
-    let match = wasmRegex.test(str, regExp);
-    console.log(match);
+    let output = document.getElementById('output');
+    try {
+        let match = wasmRegex.test(str, regExp);
+        console.log(match);
 
-    document.getElementById('output').innerText = `${str} ${match ? 'matches' : 'does not match'} ${regExp}`;
+        output.innerText = `${str} ${match ? 'matches' : 'does not match'} ${regExp}`;
+    } catch (e) {
+        console.error(e);
+        output.innerText = e.message ? `${e.kind_code ?? e.type}: ${e.message}` : e.type;
+    }
 }
\ No newline at end of file
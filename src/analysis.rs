@@ -0,0 +1,1497 @@
+use std::convert::Infallible;
+
+use regex::bytes::Regex;
+use regex_syntax::ast::{self, Ast, LiteralKind, RepetitionKind, RepetitionRange, Visitor};
+use regex_syntax::hir::{Class, Hir, HirKind, Look};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::error::ReSyntax;
+use crate::flags::Flags;
+use crate::utf16::utf16_index_bytes;
+use crate::{compile, to_js, Error};
+
+/// A span of the pattern in both UTF-8 bytes and UTF-16 code units.
+#[derive(Debug, Clone, Serialize)]
+pub struct SpanSer {
+    pub start: usize,
+    pub end: usize,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+}
+
+impl SpanSer {
+    pub fn new(pattern: &str, span: &ast::Span) -> Self {
+        SpanSer {
+            start: span.start.offset,
+            end: span.end.offset,
+            start_utf16: utf16_index_bytes(pattern, span.start.offset),
+            end_utf16: utf16_index_bytes(pattern, span.end.offset),
+        }
+    }
+}
+
+pub(crate) fn parse_ast(pattern: &str, flags: &Flags) -> Result<Ast, Error> {
+    Ok(flags
+        .ast_parser()
+        .parse(pattern)
+        .map_err(regex_syntax::Error::from)?)
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepetitionBoundSer {
+    /// How many copies of its most deeply repeated expression the nest amounts to.
+    pub product: u64,
+    #[serde(flatten)]
+    pub span: SpanSer,
+}
+
+/// Multiplies the upper bounds of nested repetitions, or the lower bound for unbounded ones,
+/// through the AST.
+struct RepetitionProduct<'p> {
+    pattern: &'p str,
+    max_product: u64,
+    products: Vec<u64>,
+    /// The outermost repetition being visited, with the largest product reached inside it.
+    nest: Option<RepetitionBoundSer>,
+    exceeded: Vec<RepetitionBoundSer>,
+}
+
+impl Visitor for RepetitionProduct<'_> {
+    type Output = Vec<RepetitionBoundSer>;
+    type Err = Infallible;
+
+    fn finish(self) -> Result<Self::Output, Self::Err> {
+        Ok(self.exceeded)
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        if let Ast::Repetition(rep) = ast {
+            let factor = match rep.op.kind {
+                RepetitionKind::ZeroOrOne
+                | RepetitionKind::ZeroOrMore
+                | RepetitionKind::OneOrMore => 1,
+                RepetitionKind::Range(RepetitionRange::Exactly(n))
+                | RepetitionKind::Range(RepetitionRange::Bounded(_, n)) => n,
+                RepetitionKind::Range(RepetitionRange::AtLeast(n)) => n.max(1),
+            };
+            let outer = self.products.last().copied().unwrap_or(1);
+            let product = outer.saturating_mul(u64::from(factor));
+            let nest = self.nest.get_or_insert_with(|| RepetitionBoundSer {
+                product,
+                span: SpanSer::new(self.pattern, &rep.span),
+            });
+            nest.product = nest.product.max(product);
+            self.products.push(product);
+        }
+        Ok(())
+    }
+
+    fn visit_post(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        if let Ast::Repetition(_) = ast {
+            self.products.pop();
+            // Only report the outermost repetition of a nest, not every one nested inside.
+            if self.products.is_empty() {
+                let nest = self.nest.take().unwrap();
+                if nest.product > self.max_product {
+                    self.exceeded.push(nest);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn check_repetition_bounds(
+    pattern: &str,
+    max_product: u64,
+) -> Result<Vec<RepetitionBoundSer>, Error> {
+    let ast = parse_ast(pattern, &Flags::default())?;
+    let visitor = RepetitionProduct {
+        pattern,
+        max_product,
+        products: Vec::new(),
+        nest: None,
+        exceeded: Vec::new(),
+    };
+    Ok(ast::visit(&ast, visitor).unwrap_or_else(|e| match e {}))
+}
+
+/// Finds nested bounded repetitions such as `(a{100}){100}` whose bounds multiply to more than
+/// `max_product`, since the compiled size of a pattern grows with that product. Unbounded
+/// repetitions count as their lower bound. Returns, for each offending nest, the span of its
+/// outermost repetition with the largest product reached inside it, or an empty array if there
+/// are none.
+#[wasm_bindgen]
+pub fn re_check_repetition_bounds(pattern: &str, max_product: u64) -> Result<JsValue, Error> {
+    Ok(to_js(&check_repetition_bounds(pattern, max_product)?))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenKind {
+    Literal,
+    /// An escaped literal such as `\.` or `\x41`.
+    Escape,
+    /// `.`, `\d`, `\p{L}` or a bracketed class.
+    Class,
+    Quantifier,
+    /// A whole group, from its `(` to its `)`.
+    Group,
+    Anchor,
+    /// A flag directive such as `(?i)`.
+    Flags,
+    Alternation,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenSer {
+    pub kind: TokenKind,
+    #[serde(flatten)]
+    pub span: SpanSer,
+}
+
+struct Tokenizer<'p> {
+    pattern: &'p str,
+    tokens: Vec<TokenSer>,
+    /// The `|` spans of each alternation being visited, innermost last.
+    pipes: Vec<std::vec::IntoIter<ast::Span>>,
+}
+
+impl Tokenizer<'_> {
+    fn push(&mut self, kind: TokenKind, span: &ast::Span) {
+        self.tokens.push(TokenSer {
+            kind,
+            span: SpanSer::new(self.pattern, span),
+        });
+    }
+}
+
+impl Visitor for Tokenizer<'_> {
+    type Output = Vec<TokenSer>;
+    type Err = Infallible;
+
+    fn finish(self) -> Result<Self::Output, Self::Err> {
+        Ok(self.tokens)
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        match ast {
+            Ast::Flags(flags) => self.push(TokenKind::Flags, &flags.span),
+            Ast::Literal(lit) => {
+                let kind = match lit.kind {
+                    LiteralKind::Verbatim => TokenKind::Literal,
+                    _ => TokenKind::Escape,
+                };
+                self.push(kind, &lit.span)
+            }
+            Ast::Dot(span) => self.push(TokenKind::Class, span),
+            Ast::Assertion(assertion) => self.push(TokenKind::Anchor, &assertion.span),
+            Ast::ClassUnicode(class) => self.push(TokenKind::Class, &class.span),
+            Ast::ClassPerl(class) => self.push(TokenKind::Class, &class.span),
+            Ast::ClassBracketed(class) => self.push(TokenKind::Class, &class.span),
+            Ast::Group(group) => self.push(TokenKind::Group, &group.span),
+            Ast::Alternation(alt) => {
+                let pipes: Vec<_> = alt
+                    .asts
+                    .windows(2)
+                    .filter_map(|pair| {
+                        let (end, next) = (pair[0].span().end, pair[1].span().start);
+                        let offset =
+                            end.offset + self.pattern[end.offset..next.offset].find('|')?;
+                        let start = ast::Position::new(offset, end.line, end.column);
+                        let end = ast::Position::new(offset + 1, end.line, end.column + 1);
+                        Some(ast::Span::new(start, end))
+                    })
+                    .collect();
+                self.pipes.push(pipes.into_iter());
+            }
+            Ast::Empty(_) | Ast::Repetition(_) | Ast::Concat(_) => {}
+        }
+        Ok(())
+    }
+
+    fn visit_post(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        match ast {
+            Ast::Repetition(rep) => self.push(TokenKind::Quantifier, &rep.op.span),
+            Ast::Alternation(_) => {
+                self.pipes.pop();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn visit_alternation_in(&mut self) -> Result<(), Self::Err> {
+        if let Some(pipe) = self.pipes.last_mut().and_then(Iterator::next) {
+            self.push(TokenKind::Alternation, &pipe);
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn tokenize(pattern: &str) -> Result<Vec<TokenSer>, Error> {
+    let ast = parse_ast(pattern, &Flags::default())?;
+    let tokenizer = Tokenizer {
+        pattern,
+        tokens: Vec::new(),
+        pipes: Vec::new(),
+    };
+    Ok(ast::visit(&ast, tokenizer).unwrap_or_else(|e| match e {}))
+}
+
+/// Splits `pattern` into tokens for syntax highlighting, see [`TokenKind`]. Tokens are in
+/// source order, except that a group comes before the tokens inside it so that a colorizer
+/// drawing them in order layers inner tokens on top. Bracketed classes are a single token.
+#[wasm_bindgen]
+pub fn re_tokenize(pattern: &str) -> Result<JsValue, Error> {
+    Ok(to_js(&tokenize(pattern)?))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuantifierKind {
+    /// `?`
+    ZeroOrOne,
+    /// `*`
+    ZeroOrMore,
+    /// `+`
+    OneOrMore,
+    /// `{m}`, `{m,}` or `{m,n}`
+    Range,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QuantifierSer {
+    pub kind: QuantifierKind,
+    pub min: u32,
+    /// `None` for unbounded quantifiers.
+    pub max: Option<u32>,
+    /// Whether the quantifier is greedy as written, without a `?` suffix. The `U` flag swaps
+    /// this.
+    pub greedy: bool,
+    /// The span of the quantifier itself, including any `?` suffix.
+    #[serde(flatten)]
+    pub span: SpanSer,
+}
+
+struct Quantifiers<'p> {
+    pattern: &'p str,
+    quantifiers: Vec<QuantifierSer>,
+}
+
+impl Visitor for Quantifiers<'_> {
+    type Output = Vec<QuantifierSer>;
+    type Err = Infallible;
+
+    fn finish(self) -> Result<Self::Output, Self::Err> {
+        Ok(self.quantifiers)
+    }
+
+    // Inner quantifiers are written before the ones they are nested in, so visiting after the
+    // children keeps source order.
+    fn visit_post(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        if let Ast::Repetition(rep) = ast {
+            let (kind, min, max) = match rep.op.kind {
+                RepetitionKind::ZeroOrOne => (QuantifierKind::ZeroOrOne, 0, Some(1)),
+                RepetitionKind::ZeroOrMore => (QuantifierKind::ZeroOrMore, 0, None),
+                RepetitionKind::OneOrMore => (QuantifierKind::OneOrMore, 1, None),
+                RepetitionKind::Range(RepetitionRange::Exactly(n)) => {
+                    (QuantifierKind::Range, n, Some(n))
+                }
+                RepetitionKind::Range(RepetitionRange::AtLeast(n)) => {
+                    (QuantifierKind::Range, n, None)
+                }
+                RepetitionKind::Range(RepetitionRange::Bounded(m, n)) => {
+                    (QuantifierKind::Range, m, Some(n))
+                }
+            };
+            self.quantifiers.push(QuantifierSer {
+                kind,
+                min,
+                max,
+                greedy: rep.greedy,
+                span: SpanSer::new(self.pattern, &rep.op.span),
+            });
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn quantifiers(pattern: &str) -> Result<Vec<QuantifierSer>, Error> {
+    let ast = parse_ast(pattern, &Flags::default())?;
+    let visitor = Quantifiers {
+        pattern,
+        quantifiers: Vec::new(),
+    };
+    Ok(ast::visit(&ast, visitor).unwrap_or_else(|e| match e {}))
+}
+
+/// Returns every quantifier of `pattern` in source order, as
+/// `{ kind, min, max, greedy, start, end, start_utf16, end_utf16 }`, for editor actions such as
+/// making one lazy. `kind` is `"zero_or_one"`, `"zero_or_more"`, `"one_or_more"` or `"range"`
+/// for `{m,n}` forms, `max` is `null` when unbounded, and the span covers the quantifier with
+/// its `?` suffix, so `a+?b{2,5}` gives a lazy `one_or_more` over `+?` and a greedy `range` from
+/// 2 to 5. Nested quantifiers like the `+` in `(a+)*` come before the ones around them.
+#[wasm_bindgen]
+pub fn re_quantifiers(pattern: &str) -> Result<JsValue, Error> {
+    Ok(to_js(&quantifiers(pattern)?))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnchorKind {
+    /// `^`, the start of the text, or of a line with the `m` flag.
+    Start,
+    /// `$`, the end of the text, or of a line with the `m` flag.
+    End,
+    /// `\b`
+    WordBoundary,
+    /// `\B`
+    NotWordBoundary,
+    /// `\A`
+    TextStart,
+    /// `\z`
+    TextEnd,
+    /// `\b{start}` or `\<`
+    WordStart,
+    /// `\b{end}` or `\>`
+    WordEnd,
+    /// `\b{start-half}`
+    WordStartHalf,
+    /// `\b{end-half}`
+    WordEndHalf,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnchorSer {
+    pub kind: AnchorKind,
+    #[serde(flatten)]
+    pub span: SpanSer,
+}
+
+struct Anchors<'p> {
+    pattern: &'p str,
+    anchors: Vec<AnchorSer>,
+}
+
+impl Visitor for Anchors<'_> {
+    type Output = Vec<AnchorSer>;
+    type Err = Infallible;
+
+    fn finish(self) -> Result<Self::Output, Self::Err> {
+        Ok(self.anchors)
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        if let Ast::Assertion(assertion) = ast {
+            let kind = match assertion.kind {
+                ast::AssertionKind::StartLine => AnchorKind::Start,
+                ast::AssertionKind::EndLine => AnchorKind::End,
+                ast::AssertionKind::StartText => AnchorKind::TextStart,
+                ast::AssertionKind::EndText => AnchorKind::TextEnd,
+                ast::AssertionKind::WordBoundary => AnchorKind::WordBoundary,
+                ast::AssertionKind::NotWordBoundary => AnchorKind::NotWordBoundary,
+                ast::AssertionKind::WordBoundaryStart
+                | ast::AssertionKind::WordBoundaryStartAngle => AnchorKind::WordStart,
+                ast::AssertionKind::WordBoundaryEnd | ast::AssertionKind::WordBoundaryEndAngle => {
+                    AnchorKind::WordEnd
+                }
+                ast::AssertionKind::WordBoundaryStartHalf => AnchorKind::WordStartHalf,
+                ast::AssertionKind::WordBoundaryEndHalf => AnchorKind::WordEndHalf,
+            };
+            self.anchors.push(AnchorSer {
+                kind,
+                span: SpanSer::new(self.pattern, &assertion.span),
+            });
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn anchors(pattern: &str) -> Result<Vec<AnchorSer>, Error> {
+    let ast = parse_ast(pattern, &Flags::default())?;
+    let visitor = Anchors {
+        pattern,
+        anchors: Vec::new(),
+    };
+    Ok(ast::visit(&ast, visitor).unwrap_or_else(|e| match e {}))
+}
+
+/// Returns every anchor and zero-width assertion of `pattern` in source order, as
+/// `{ kind, start, end, start_utf16, end_utf16 }`, to explain where matches can land. `kind` is
+/// `"start"` or `"end"` for `^` and `$`, `"text_start"` or `"text_end"` for `\A` and `\z`,
+/// `"word_boundary"` or `"not_word_boundary"` for `\b` and `\B`, and `"word_start"`,
+/// `"word_end"`, `"word_start_half"` or `"word_end_half"` for the `\b{...}` forms, so
+/// `^\bword\b$` gives a start, two word boundaries and an end.
+#[wasm_bindgen]
+pub fn re_anchors(pattern: &str) -> Result<JsValue, Error> {
+    Ok(to_js(&anchors(pattern)?))
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupSpanSer<'p> {
+    /// The capture index, `None` for non-capturing groups.
+    pub index: Option<u32>,
+    pub name: Option<&'p str>,
+    pub capturing: bool,
+    #[serde(flatten)]
+    pub span: SpanSer,
+}
+
+struct GroupSpans<'p> {
+    pattern: &'p str,
+    groups: Vec<GroupSpanSer<'p>>,
+}
+
+impl<'p> Visitor for GroupSpans<'p> {
+    type Output = Vec<GroupSpanSer<'p>>;
+    type Err = Infallible;
+
+    fn finish(self) -> Result<Self::Output, Self::Err> {
+        Ok(self.groups)
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        if let Ast::Group(group) = ast {
+            let name = match &group.kind {
+                ast::GroupKind::CaptureName { name, .. } => {
+                    Some(&self.pattern[name.span.start.offset..name.span.end.offset])
+                }
+                _ => None,
+            };
+            self.groups.push(GroupSpanSer {
+                index: group.capture_index(),
+                name,
+                capturing: group.capture_index().is_some(),
+                span: SpanSer::new(self.pattern, &group.span),
+            });
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn group_spans<'p>(
+    pattern: &'p str,
+    flags: &Flags,
+) -> Result<Vec<GroupSpanSer<'p>>, Error> {
+    let ast = parse_ast(pattern, flags)?;
+    let visitor = GroupSpans {
+        pattern,
+        groups: Vec::new(),
+    };
+    Ok(ast::visit(&ast, visitor).unwrap_or_else(|e| match e {}))
+}
+
+/// Returns every `(...)` group of `pattern` in source order, each as
+/// `{ index, name, capturing, start, end, start_utf16, end_utf16 }` spanning its parens.
+/// Non-capturing groups such as `(?:...)` and `(?i:...)` have a `null` index. Flag directives
+/// like `(?i)` are not groups.
+#[wasm_bindgen]
+pub fn re_group_spans(pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    Ok(to_js(&group_spans(pattern, &Flags::parse(flags))?))
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateNameSer<'p> {
+    pub name: &'p str,
+    /// The span of the name in each group using it, in source order.
+    pub spans: Vec<SpanSer>,
+}
+
+/// Chars a group name can start with, used to make up names.
+const NAME_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ_abcdefghijklmnopqrstuvwxyz";
+
+/// A group name of `len` bytes that `pattern` doesn't use, so that renaming a group to it keeps
+/// every offset of the pattern.
+fn unused_name(pattern: &str, len: usize) -> Option<String> {
+    (0..)
+        .map_while(|mut n: usize| {
+            let mut name = String::with_capacity(len);
+            for _ in 0..len {
+                name.push(NAME_CHARS[n % NAME_CHARS.len()] as char);
+                n /= NAME_CHARS.len();
+            }
+            // Past the last name of this length.
+            (n == 0).then_some(name)
+        })
+        .find(|name| !pattern.contains(&format!("<{}>", name)))
+}
+
+/// The parser stops at the first duplicate name, so each one found is renamed in a copy of the
+/// pattern before parsing again.
+pub(crate) fn check_duplicate_names(pattern: &str) -> Result<Vec<DuplicateNameSer<'_>>, Error> {
+    let mut renamed = pattern.to_string();
+    let mut duplicates: Vec<DuplicateNameSer> = Vec::new();
+    loop {
+        let e = match Flags::default().ast_parser().parse(&renamed) {
+            Ok(_) => break,
+            Err(e) => e,
+        };
+        let ast::ErrorKind::GroupNameDuplicate { original } = e.kind() else {
+            if duplicates.is_empty() {
+                return Err(regex_syntax::Error::from(e).into());
+            }
+            break;
+        };
+        let span = e.span();
+        let name = &pattern[span.start.offset..span.end.offset];
+        match duplicates.iter_mut().find(|d| d.name == name) {
+            Some(d) => d.spans.push(SpanSer::new(pattern, span)),
+            None => duplicates.push(DuplicateNameSer {
+                name,
+                spans: vec![SpanSer::new(pattern, original), SpanSer::new(pattern, span)],
+            }),
+        }
+        let Some(unused) = unused_name(&renamed, name.len()) else {
+            break;
+        };
+        renamed.replace_range(span.start.offset..span.end.offset, &unused);
+    }
+    Ok(duplicates)
+}
+
+/// Returns every group name `pattern` uses more than once, as `{ name, spans }` with the span of
+/// the name in each group using it, so an editor can point at all of them instead of only the
+/// second one the compile error names. Empty if every name is unique. Another syntax error is
+/// returned as an error if it comes before the first duplicate, and otherwise ends the search.
+#[wasm_bindgen]
+pub fn re_check_duplicate_names(pattern: &str) -> Result<JsValue, Error> {
+    Ok(to_js(&check_duplicate_names(pattern)?))
+}
+
+/// `n` in Unicode subscript digits.
+fn subscript(n: u32) -> String {
+    n.to_string()
+        .chars()
+        .map(|d| char::from_u32(0x2080 + d.to_digit(10).unwrap()).unwrap())
+        .collect()
+}
+
+pub(crate) fn number_groups(
+    pattern: &str,
+    prefix: Option<&str>,
+    suffix: Option<&str>,
+) -> Result<String, Error> {
+    let mut numbered = String::with_capacity(pattern.len());
+    let mut last = 0;
+    for group in group_spans(pattern, &Flags::default())? {
+        let Some(index) = group.index else { continue };
+        let after_paren = group.span.start + 1;
+        numbered.push_str(&pattern[last..after_paren]);
+        match (prefix, suffix) {
+            (None, None) => numbered.push_str(&subscript(index)),
+            _ => {
+                numbered.push_str(prefix.unwrap_or(""));
+                numbered.push_str(&index.to_string());
+                numbered.push_str(suffix.unwrap_or(""));
+            }
+        }
+        last = after_paren;
+    }
+    numbered.push_str(&pattern[last..]);
+    Ok(numbered)
+}
+
+/// Returns `pattern` with the index of each capturing group written after its opening paren,
+/// as in `(₁a(₂b))`, to show which `$N` refers to which group. Numbers are in subscript digits
+/// unless `prefix` or `suffix` is given, in which case they are plain digits between the two,
+/// so `"#"` and `":"` give `(#1:a(#2:b))`. Non-capturing groups are left as is. The result is
+/// meant for display and is usually not a valid pattern.
+#[wasm_bindgen]
+pub fn re_number_groups(
+    pattern: &str,
+    prefix: Option<String>,
+    suffix: Option<String>,
+) -> Result<String, Error> {
+    number_groups(pattern, prefix.as_deref(), suffix.as_deref())
+}
+
+/// Records in `optional`, indexed by group number, whether each capture group in `hir` can be
+/// left out of a match, all of them if `outer` is.
+fn collect_optional(hir: &Hir, outer: bool, optional: &mut [bool]) {
+    match hir.kind() {
+        HirKind::Capture(capture) => {
+            optional[capture.index as usize] = outer;
+            collect_optional(&capture.sub, outer, optional);
+        }
+        HirKind::Repetition(repetition) => {
+            collect_optional(&repetition.sub, outer || repetition.min == 0, optional);
+        }
+        HirKind::Concat(hirs) => {
+            for hir in hirs {
+                collect_optional(hir, outer, optional);
+            }
+        }
+        // Each group is in one branch, and the others can match without it.
+        HirKind::Alternation(hirs) => {
+            for hir in hirs {
+                collect_optional(hir, true, optional);
+            }
+        }
+        HirKind::Empty | HirKind::Literal(_) | HirKind::Class(_) | HirKind::Look(_) => {}
+    }
+}
+
+/// The groups of `hir`, compiled to `re`, that may not participate in a match.
+pub(crate) fn optional_groups(re: &Regex, hir: &Hir) -> Vec<usize> {
+    // Groups repeated `{0}` times are dropped from the HIR and never participate.
+    let mut optional = vec![true; re.captures_len()];
+    collect_optional(hir, false, &mut optional);
+    (1..optional.len()).filter(|&i| optional[i]).collect()
+}
+
+/// Returns the indices of the capture groups that may not participate in a match, in ascending
+/// order, to warn that a `$name` reference to them can expand to nothing. A group is optional
+/// when it is inside a quantifier that allows zero repetitions, such as `?`, `*` or `{0,3}`, or
+/// inside one branch of an alternation, so in `(a)(b)?` only group 2 is. This is structural: a
+/// group that can only be skipped when the match fails anyway is still reported.
+#[wasm_bindgen]
+pub fn re_optional_groups(pattern: &str, flags: &str) -> Result<Vec<usize>, Error> {
+    let flags = Flags::parse(flags);
+    let hir = flags.parser().parse(pattern)?;
+    let re = compile(pattern, &flags)?;
+    Ok(optional_groups(&re, &hir))
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is the same on every platform and release.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+pub(crate) fn behavior_hash(pattern: &str, flags: &Flags) -> Result<String, Error> {
+    let hir = flags.parser().parse(pattern)?;
+    Ok(format!("{:016x}", fnv1a(hir.to_string().as_bytes())))
+}
+
+/// Returns a hex hash of what `pattern` matches under `flags`, for caching results by the
+/// effective regex rather than its source. The hash is taken over the pattern's HIR, after
+/// flags are applied and literals and classes are normalized, so `(?i)abc` and `ABC` with the
+/// `i` flag hash the same. Equal hashes mean structurally equal HIR, not every pair of patterns
+/// that match the same strings. Hashes may change when the regex crate is upgraded.
+#[wasm_bindgen]
+pub fn re_behavior_hash(pattern: &str, flags: &str) -> Result<String, Error> {
+    behavior_hash(pattern, &Flags::parse(flags))
+}
+
+/// Where an inline flag directive applies.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlagScope {
+    /// `(?flags)`: up to the end of the enclosing group, or of the pattern.
+    Rest,
+    /// `(?flags:...)`: inside the group only.
+    Group,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FlagSpanSer<'p> {
+    /// The flags as written, such as `i` or `i-s`.
+    pub flags: &'p str,
+    pub scope: FlagScope,
+    /// The whole directive for `rest`, the whole group for `group`.
+    #[serde(flatten)]
+    pub span: SpanSer,
+}
+
+struct InlineFlags<'p> {
+    pattern: &'p str,
+    spans: Vec<FlagSpanSer<'p>>,
+}
+
+impl InlineFlags<'_> {
+    fn push(&mut self, flags: &ast::Flags, scope: FlagScope, span: &ast::Span) {
+        self.spans.push(FlagSpanSer {
+            flags: &self.pattern[flags.span.start.offset..flags.span.end.offset],
+            scope,
+            span: SpanSer::new(self.pattern, span),
+        });
+    }
+}
+
+impl<'p> Visitor for InlineFlags<'p> {
+    type Output = Vec<FlagSpanSer<'p>>;
+    type Err = Infallible;
+
+    fn finish(self) -> Result<Self::Output, Self::Err> {
+        Ok(self.spans)
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        match ast {
+            Ast::Flags(set) => self.push(&set.flags, FlagScope::Rest, &set.span),
+            Ast::Group(group) => {
+                if let ast::GroupKind::NonCapturing(flags) = &group.kind {
+                    if !flags.items.is_empty() {
+                        self.push(flags, FlagScope::Group, &group.span);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn inline_flag_spans(pattern: &str) -> Result<Vec<FlagSpanSer<'_>>, Error> {
+    let ast = parse_ast(pattern, &Flags::default())?;
+    let visitor = InlineFlags {
+        pattern,
+        spans: Vec::new(),
+    };
+    Ok(ast::visit(&ast, visitor).unwrap_or_else(|e| match e {}))
+}
+
+/// Returns every inline flag directive of `pattern` in source order, as
+/// `{ flags, scope, start, end, start_utf16, end_utf16 }`. `flags` is written as in the pattern,
+/// with `-` before negated flags. A `"rest"` directive like `(?i)` spans itself and applies up
+/// to the end of the group containing it, a `"group"` one like `(?-i:...)` spans the group it
+/// applies to. Plain `(?:...)` groups set no flags and are skipped.
+#[wasm_bindgen]
+pub fn re_inline_flag_spans(pattern: &str) -> Result<JsValue, Error> {
+    Ok(to_js(&inline_flag_spans(pattern)?))
+}
+
+/// Stops at the first construct whose meaning depends on the `u` flag.
+struct UsesUnicode;
+
+impl Visitor for UsesUnicode {
+    type Output = bool;
+    /// Signals a Unicode-dependent construct, ending the visit early.
+    type Err = ();
+
+    fn finish(self) -> Result<Self::Output, Self::Err> {
+        Ok(false)
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        match ast {
+            Ast::Literal(literal) if !literal.c.is_ascii() => Err(()),
+            Ast::ClassUnicode(_) | Ast::ClassPerl(_) => Err(()),
+            Ast::Assertion(assertion) => match assertion.kind {
+                ast::AssertionKind::StartLine
+                | ast::AssertionKind::EndLine
+                | ast::AssertionKind::StartText
+                | ast::AssertionKind::EndText => Ok(()),
+                _ => Err(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn visit_class_set_item_pre(&mut self, item: &ast::ClassSetItem) -> Result<(), Self::Err> {
+        match item {
+            ast::ClassSetItem::Literal(literal) if !literal.c.is_ascii() => Err(()),
+            ast::ClassSetItem::Range(range) if !range.end.c.is_ascii() => Err(()),
+            ast::ClassSetItem::Unicode(_) | ast::ClassSetItem::Perl(_) => Err(()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether `pattern` has a construct the `u` flag changes the meaning of, parsed with `flags` so
+/// that comments and whitespace of the `x` flag are skipped.
+pub(crate) fn uses_unicode(pattern: &str, flags: &Flags) -> Result<bool, Error> {
+    let ast = parse_ast(pattern, flags)?;
+    Ok(ast::visit(&ast, UsesUnicode).unwrap_or(true))
+}
+
+/// Returns whether `pattern` has a construct that the `u` flag changes the meaning of, to hint
+/// that it should be set: a Unicode class like `\p{L}`, a non-ASCII char, a Perl class such as
+/// `\w` or `\d`, which only cover ASCII without the flag, or a word boundary like `\b`, which
+/// relies on `\w`. `.` and case-insensitive matching also differ on non-ASCII input but are not
+/// counted, as they rarely decide whether the flag is wanted.
+#[wasm_bindgen]
+pub fn re_uses_unicode(pattern: &str) -> Result<bool, Error> {
+    uses_unicode(pattern, &Flags::default())
+}
+
+/// Collects a note on each construct of a pattern that the `u` flag changes the meaning of.
+#[derive(Default)]
+struct UnicodeNotes {
+    notes: Vec<&'static str>,
+}
+
+impl UnicodeNotes {
+    fn add(&mut self, note: &'static str) {
+        if !self.notes.contains(&note) {
+            self.notes.push(note);
+        }
+    }
+
+    fn perl(&mut self, class: &ast::ClassPerl) {
+        self.add(match class.kind {
+            ast::ClassPerlKind::Digit => {
+                "\\d includes all Unicode decimal digits when unicode is on"
+            }
+            ast::ClassPerlKind::Space => "\\s includes Unicode whitespace when unicode is on",
+            ast::ClassPerlKind::Word => "\\w includes Unicode letters when unicode is on",
+        });
+    }
+}
+
+impl Visitor for UnicodeNotes {
+    type Output = Vec<&'static str>;
+    type Err = Infallible;
+
+    fn finish(self) -> Result<Self::Output, Self::Err> {
+        Ok(self.notes)
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        match ast {
+            Ast::Dot(_) => {
+                self.add(". matches a whole char when unicode is on and a byte when off")
+            }
+            Ast::ClassPerl(class) => self.perl(class),
+            Ast::ClassBracketed(class) if class.negated => self
+                .add("negated classes match a whole char when unicode is on and a byte when off"),
+            Ast::Assertion(assertion) => match assertion.kind {
+                ast::AssertionKind::WordBoundary | ast::AssertionKind::NotWordBoundary => {
+                    self.add("\\b and \\B use the Unicode \\w when unicode is on")
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn visit_class_set_item_pre(&mut self, item: &ast::ClassSetItem) -> Result<(), Self::Err> {
+        if let ast::ClassSetItem::Perl(class) = item {
+            self.perl(class);
+        }
+        Ok(())
+    }
+}
+
+/// The ranges of `class` as code points or bytes, so a class of ASCII ranges is the same
+/// whichever mode it was translated in.
+fn class_ranges(class: &Class) -> Vec<(u32, u32)> {
+    match class {
+        Class::Unicode(class) => class
+            .iter()
+            .map(|r| (u32::from(r.start()), u32::from(r.end())))
+            .collect(),
+        Class::Bytes(class) => class
+            .iter()
+            .map(|r| (u32::from(r.start()), u32::from(r.end())))
+            .collect(),
+    }
+}
+
+/// Whether `a` and `b` have the same structure, comparing classes by their ranges.
+fn same_hir(a: &Hir, b: &Hir) -> bool {
+    let all_same =
+        |a: &[Hir], b: &[Hir]| a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_hir(a, b));
+    match (a.kind(), b.kind()) {
+        (HirKind::Empty, HirKind::Empty) => true,
+        (HirKind::Literal(a), HirKind::Literal(b)) => a == b,
+        (HirKind::Class(a), HirKind::Class(b)) => class_ranges(a) == class_ranges(b),
+        (HirKind::Look(a), HirKind::Look(b)) => a == b,
+        (HirKind::Repetition(a), HirKind::Repetition(b)) => {
+            (a.min, a.max, a.greedy) == (b.min, b.max, b.greedy) && same_hir(&a.sub, &b.sub)
+        }
+        (HirKind::Capture(a), HirKind::Capture(b)) => {
+            (a.index, &a.name) == (b.index, &b.name) && same_hir(&a.sub, &b.sub)
+        }
+        (HirKind::Concat(a), HirKind::Concat(b)) => all_same(a, b),
+        (HirKind::Alternation(a), HirKind::Alternation(b)) => all_same(a, b),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnicodeDiffSer {
+    /// Whether the pattern means something else with the `u` flag than without it.
+    pub differs: bool,
+    /// What changes, empty if nothing does.
+    pub description: String,
+    /// Set when the pattern only compiles in one of the two modes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unicode_error: Option<ReSyntax>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ascii_error: Option<ReSyntax>,
+}
+
+pub(crate) fn unicode_diff(pattern: &str) -> Result<UnicodeDiffSer, Error> {
+    let parse = |unicode| {
+        let flags = Flags {
+            unicode,
+            ..Flags::default()
+        };
+        flags.parser().parse(pattern).map_err(ReSyntax::from)
+    };
+    let (differs, description, unicode_error, ascii_error) = match (parse(true), parse(false)) {
+        (Ok(unicode), Ok(ascii)) if same_hir(&unicode, &ascii) => {
+            (false, String::new(), None, None)
+        }
+        (Ok(_), Ok(_)) => {
+            let ast = parse_ast(pattern, &Flags::default())?;
+            let notes = ast::visit(&ast, UnicodeNotes::default()).unwrap_or_else(|e| match e {});
+            let description = match notes.is_empty() {
+                true => "non-ASCII input matches differently when unicode is on".to_string(),
+                false => notes.join("; "),
+            };
+            (true, description, None, None)
+        }
+        (Ok(_), Err(e)) => {
+            let description = "only compiles when unicode is on".to_string();
+            (true, description, None, Some(e))
+        }
+        (Err(e), Ok(_)) => {
+            let description = "only compiles when unicode is off".to_string();
+            (true, description, Some(e), None)
+        }
+        (Err(e), Err(_)) => return Err(e.into()),
+    };
+    Ok(UnicodeDiffSer {
+        differs,
+        description,
+        unicode_error,
+        ascii_error,
+    })
+}
+
+/// Returns `{ differs, description, unicode_error, ascii_error }` telling how the `u` flag
+/// changes what `pattern` means, for teaching the flag. The pattern is translated with and
+/// without Unicode mode and the results compared, with classes compared by their ranges, so
+/// `\w` differs, as it covers Unicode letters in Unicode mode, while `abc` and `[a-z]` do not.
+/// `description` is a short explanation of each construct behind a difference. If the pattern
+/// only compiles in one mode, the error of the other is reported as `unicode_error` or
+/// `ascii_error`, and if it compiles in neither, the Unicode mode error is returned.
+#[wasm_bindgen]
+pub fn re_unicode_diff(pattern: &str) -> Result<JsValue, Error> {
+    Ok(to_js(&unicode_diff(pattern)?))
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatternInfoSer {
+    /// The number of groups, including group 0 for the whole match.
+    pub capture_count: usize,
+    /// The name of each group by number, `null` for unnamed groups and group 0.
+    pub names: Vec<Option<String>>,
+    pub matches_empty: bool,
+    pub fully_anchored: bool,
+    pub uses_unicode: bool,
+    /// Bounds on the length of a match in UTF-8 bytes, `null` where none is known: for
+    /// `max_len` when repetition is unbounded, and for both when a part of the pattern can
+    /// never match, such as `[^\s\S]`.
+    pub min_len: Option<usize>,
+    pub max_len: Option<usize>,
+}
+
+pub(crate) fn pattern_info(pattern: &str, flags: &Flags) -> Result<PatternInfoSer, Error> {
+    let hir = flags.parser().parse(pattern)?;
+    let re = compile(pattern, flags)?;
+    let props = hir.properties();
+    Ok(PatternInfoSer {
+        capture_count: re.captures_len(),
+        names: re.capture_names().map(|n| n.map(str::to_owned)).collect(),
+        matches_empty: re.is_match(b""),
+        fully_anchored: props.look_set_prefix().contains(Look::Start)
+            && props.look_set_suffix().contains(Look::End),
+        uses_unicode: uses_unicode(pattern, flags)?,
+        min_len: props.minimum_len(),
+        max_len: props.maximum_len(),
+    })
+}
+
+/// Returns `{ capture_count, names, matches_empty, fully_anchored, uses_unicode, min_len,
+/// max_len }` for a rule's metadata panel in one call. `matches_empty` is whether the pattern
+/// matches the empty input, as for `re_empty_behavior`, `fully_anchored` whether every match
+/// spans the whole input, as with `^...$` without the `m` flag, and `uses_unicode` is as for
+/// [`re_uses_unicode`]. So `^(?P<a>\d{2,4})$` has 2 groups named `[null, "a"]`, is fully
+/// anchored and uses Unicode, and matches between 2 and 4 bytes without the `u` flag.
+#[wasm_bindgen]
+pub fn re_pattern_info(pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    Ok(to_js(&pattern_info(pattern, &flags)?))
+}
+
+/// Collects the rewrites that make the word-dependent constructs of a pattern ASCII-only.
+struct AsciiWord<'p> {
+    pattern: &'p str,
+    /// Spans of the pattern with their replacements, in source order.
+    edits: Vec<(ast::Span, String)>,
+}
+
+impl AsciiWord<'_> {
+    fn perl(&mut self, class: &ast::ClassPerl, in_bracket: bool) {
+        if class.kind != ast::ClassPerlKind::Word {
+            return;
+        }
+        let replacement = match (in_bracket, class.negated) {
+            (false, false) => "[[:word:]]",
+            (false, true) => "[^[:word:]]",
+            (true, false) => "[:word:]",
+            (true, true) => "[:^word:]",
+        };
+        self.edits.push((class.span, replacement.to_string()));
+    }
+}
+
+impl Visitor for AsciiWord<'_> {
+    type Output = Vec<(ast::Span, String)>;
+    type Err = Infallible;
+
+    fn finish(self) -> Result<Self::Output, Self::Err> {
+        Ok(self.edits)
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        match ast {
+            Ast::ClassPerl(class) => self.perl(class, false),
+            Ast::Assertion(assertion) => match assertion.kind {
+                ast::AssertionKind::StartLine
+                | ast::AssertionKind::EndLine
+                | ast::AssertionKind::StartText
+                | ast::AssertionKind::EndText => {}
+                _ => {
+                    let span = assertion.span;
+                    let written = &self.pattern[span.start.offset..span.end.offset];
+                    self.edits.push((span, format!("(?-u:{})", written)));
+                }
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn visit_class_set_item_pre(&mut self, item: &ast::ClassSetItem) -> Result<(), Self::Err> {
+        if let ast::ClassSetItem::Perl(class) = item {
+            self.perl(class, true);
+        }
+        Ok(())
+    }
+}
+
+/// `pattern` with `\w`, `\W` and every word boundary assertion replaced by their ASCII forms,
+/// for the `w` flag. Word classes become `[:word:]` classes, which are ASCII-only whatever the
+/// `u` flag, and assertions are wrapped in `(?-u:...)`.
+pub(crate) fn ascii_word(pattern: &str, flags: &Flags) -> Result<String, Error> {
+    let ast = parse_ast(pattern, flags)?;
+    let visitor = AsciiWord {
+        pattern,
+        edits: Vec::new(),
+    };
+    let mut edits = ast::visit(&ast, visitor).unwrap_or_else(|e| match e {});
+    edits.sort_by_key(|(span, _)| span.start.offset);
+    let mut rewritten = String::with_capacity(pattern.len());
+    let mut last = 0;
+    for (span, replacement) in edits {
+        rewritten.push_str(&pattern[last..span.start.offset]);
+        rewritten.push_str(&replacement);
+        last = span.end.offset;
+    }
+    rewritten.push_str(&pattern[last..]);
+    Ok(rewritten)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_spans_include_non_capturing_groups() {
+        let groups = group_spans("(a)(?:b)(?P<c>c)", &Flags::default()).unwrap();
+        let summary: Vec<_> = groups
+            .iter()
+            .map(|g| (g.index, g.name, g.capturing, g.span.start, g.span.end))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Some(1), None, true, 0, 3),
+                (None, None, false, 3, 8),
+                (Some(2), Some("c"), true, 8, 16),
+            ]
+        );
+    }
+
+    #[test]
+    fn group_spans_skip_flag_directives() {
+        let groups = group_spans("(?i)é(?s:(x))", &Flags::default()).unwrap();
+        let spans: Vec<_> = groups
+            .iter()
+            .map(|g| (g.index, g.span.start_utf16, g.span.end_utf16))
+            .collect();
+        assert_eq!(spans, [(None, 5, 13), (Some(1), 9, 12)]);
+    }
+
+    fn hash(pattern: &str, flags: &str) -> String {
+        behavior_hash(pattern, &Flags::parse(flags)).unwrap()
+    }
+
+    #[test]
+    fn behavior_hash_is_equal_for_equivalent_patterns() {
+        assert_eq!(hash("(?i)abc", ""), hash("ABC", "i"));
+        assert_eq!(hash("[a-cb]", ""), hash("[abc]", ""));
+        assert_eq!(hash("a{1}", ""), hash("a", ""));
+        assert_eq!(hash("(?x) a b ", ""), hash("ab", ""));
+        assert_eq!(hash("ab", "").len(), 16);
+    }
+
+    #[test]
+    fn behavior_hash_differs_with_behavior() {
+        assert_ne!(hash("abc", ""), hash("abc", "i"));
+        assert_ne!(hash("a+", ""), hash("a*", ""));
+        assert_ne!(hash(r"\w", ""), hash(r"\w", "u"));
+    }
+
+    #[test]
+    fn inline_flag_spans_report_scope_and_sign() {
+        let spans = inline_flag_spans("(?i)a(?-i:b)(?:c)(?s-m)").unwrap();
+        let summary: Vec<_> = spans
+            .iter()
+            .map(|s| {
+                (
+                    s.flags,
+                    matches!(s.scope, FlagScope::Rest),
+                    s.span.start,
+                    s.span.end,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("i", true, 0, 4),
+                ("-i", false, 5, 12),
+                ("s-m", true, 17, 23)
+            ]
+        );
+    }
+
+    #[test]
+    fn uses_unicode_spots_unicode_constructs() {
+        for pattern in [r"\p{L}", "café", r"[à-ü]", r"\w+", r"\bword", r"[\d.]"] {
+            assert!(
+                uses_unicode(pattern, &Flags::default()).unwrap(),
+                "{pattern}"
+            );
+        }
+        for pattern in ["[a-z]+", "(?i)abc.", r"\.\$", ""] {
+            assert!(
+                !uses_unicode(pattern, &Flags::default()).unwrap(),
+                "{pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn ascii_word_rewrites_word_classes_and_boundaries() {
+        let flags = Flags::parse("u");
+        let rewritten = ascii_word(r"\bé\w[\W\d]\B^$", &flags).unwrap();
+        assert_eq!(rewritten, r"(?-u:\b)é[[:word:]][[:^word:]\d](?-u:\B)^$");
+    }
+
+    #[test]
+    fn w_flag_keeps_literals_unicode() {
+        let is_match = |pattern, flags| {
+            let re = compile(pattern, &Flags::parse(flags)).unwrap();
+            re.is_match("café".as_bytes())
+        };
+        // `é` is not a word char, so there is a boundary between `f` and `é`.
+        assert!(is_match(r"\bcaf\b", "wu"));
+        assert!(!is_match(r"\bcaf\b", "u"));
+        assert!(is_match(r"caf\W$", "wu"));
+        assert!(is_match(r"^caf.$", "wu"));
+        assert!(is_match(r"É", "wui"));
+    }
+
+    fn duplicate_spans(pattern: &str) -> Vec<(&str, Vec<(usize, usize)>)> {
+        let duplicates = check_duplicate_names(pattern).unwrap();
+        duplicates
+            .into_iter()
+            .map(|d| {
+                (
+                    d.name,
+                    d.spans
+                        .iter()
+                        .map(|s| (s.start_utf16, s.end_utf16))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_duplicate_names_reports_every_use() {
+        assert_eq!(
+            duplicate_spans("(?P<a>x)(?P<a>y)"),
+            [("a", vec![(4, 5), (12, 13)])]
+        );
+        assert_eq!(
+            duplicate_spans("é(?<bb>x)(?<c>)(?<bb>y)(?<c>)(?<bb>)"),
+            [
+                ("bb", vec![(4, 6), (18, 20), (32, 34)]),
+                ("c", vec![(12, 13), (26, 27)])
+            ]
+        );
+        assert!(duplicate_spans("(?P<a>x)(?P<b>y)").is_empty());
+    }
+
+    #[test]
+    fn check_duplicate_names_passes_on_other_errors() {
+        assert!(matches!(
+            check_duplicate_names("(?P<a>x"),
+            Err(Error::Syntax(_))
+        ));
+        // Once a duplicate is found, later errors don't hide it.
+        assert_eq!(
+            duplicate_spans("(?<a>)(?<a>)("),
+            [("a", vec![(3, 4), (9, 10)])]
+        );
+    }
+
+    #[test]
+    fn number_groups_numbers_nested_groups_in_order() {
+        assert_eq!(
+            number_groups("(a(b))(?:c)(?P<d>é)", None, None).unwrap(),
+            "(₁a(₂b))(?:c)(₃?P<d>é)"
+        );
+        assert_eq!(
+            number_groups(r"\((x)", Some("#"), Some(":")).unwrap(),
+            r"\((#1:x)"
+        );
+        let many = "()".repeat(12);
+        assert!(number_groups(&many, None, None).unwrap().ends_with("(₁₂)"));
+    }
+
+    fn quantifier_summary(
+        pattern: &str,
+    ) -> Vec<(QuantifierKind, u32, Option<u32>, bool, usize, usize)> {
+        quantifiers(pattern)
+            .unwrap()
+            .iter()
+            .map(|q| {
+                (
+                    q.kind,
+                    q.min,
+                    q.max,
+                    q.greedy,
+                    q.span.start_utf16,
+                    q.span.end_utf16,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn quantifiers_report_bounds_and_greediness() {
+        assert_eq!(
+            quantifier_summary("a+?b{2,5}"),
+            [
+                (QuantifierKind::OneOrMore, 1, None, false, 1, 3),
+                (QuantifierKind::Range, 2, Some(5), true, 4, 9)
+            ]
+        );
+        assert_eq!(
+            quantifier_summary("é{3}x{1,}?"),
+            [
+                (QuantifierKind::Range, 3, Some(3), true, 1, 4),
+                (QuantifierKind::Range, 1, None, false, 5, 10)
+            ]
+        );
+    }
+
+    #[test]
+    fn quantifiers_are_in_source_order_when_nested_or_stacked() {
+        let kinds: Vec<_> = quantifier_summary("(a*)?(?:b?)+")
+            .iter()
+            .map(|&(kind, _, _, _, start, _)| (kind, start))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (QuantifierKind::ZeroOrMore, 2),
+                (QuantifierKind::ZeroOrOne, 4),
+                (QuantifierKind::ZeroOrOne, 9),
+                (QuantifierKind::OneOrMore, 11),
+            ]
+        );
+    }
+
+    fn optional(pattern: &str) -> Vec<usize> {
+        let flags = Flags::default();
+        let hir = flags.parser().parse(pattern).unwrap();
+        optional_groups(&compile(pattern, &flags).unwrap(), &hir)
+    }
+
+    #[test]
+    fn optional_groups_are_those_that_can_be_skipped() {
+        assert_eq!(optional("(a)(b)?"), [2]);
+        assert_eq!(optional("(a)|(b)"), [1, 2]);
+        assert_eq!(optional("(?:(a)(b)*)+(c){1,2}"), [2]);
+        // Nested groups inherit the optionality of the groups around them.
+        assert_eq!(optional("((a)(b))?(c)"), [1, 2, 3]);
+        assert_eq!(optional("(a){0}(b)"), [1]);
+    }
+
+    #[test]
+    fn pattern_info_describes_an_anchored_pattern() {
+        let info = pattern_info(r"^(?P<a>\d{2,4})$", &Flags::default()).unwrap();
+        assert_eq!(info.capture_count, 2);
+        assert_eq!(info.names, [None, Some("a".to_owned())]);
+        assert!(!info.matches_empty);
+        assert!(info.fully_anchored);
+        assert!(info.uses_unicode);
+        assert_eq!((info.min_len, info.max_len), (Some(2), Some(4)));
+    }
+
+    #[test]
+    fn pattern_info_parses_with_the_x_flag() {
+        let pattern = "a # a comment with a ) paren";
+        assert!(pattern_info(pattern, &Flags::default()).is_err());
+        let info = pattern_info(pattern, &Flags::parse("x")).unwrap();
+        assert_eq!(info.capture_count, 1);
+        assert!(!info.uses_unicode);
+        assert!(!info.fully_anchored);
+        assert_eq!((info.min_len, info.max_len), (Some(1), Some(1)));
+    }
+
+    #[test]
+    fn unicode_diff_compares_classes_by_range() {
+        let diff = unicode_diff(r"\w+").unwrap();
+        assert!(diff.differs);
+        assert!(!diff.description.is_empty());
+        assert!(diff.unicode_error.is_none() && diff.ascii_error.is_none());
+        for pattern in ["abc", "[a-z]+"] {
+            let diff = unicode_diff(pattern).unwrap();
+            assert!(!diff.differs, "{pattern}");
+            assert_eq!(diff.description, "");
+        }
+    }
+
+    #[test]
+    fn unicode_diff_reports_the_mode_that_fails() {
+        let diff = unicode_diff(r"\p{L}").unwrap();
+        assert!(diff.differs);
+        assert_eq!(diff.description, "only compiles when unicode is on");
+        assert!(diff.unicode_error.is_none());
+        assert!(diff.ascii_error.is_some());
+        assert!(matches!(unicode_diff("(a"), Err(Error::Syntax(_))));
+    }
+
+    #[test]
+    fn anchors_lists_assertions_in_source_order() {
+        let kinds = |pattern| {
+            let anchors = anchors(pattern).unwrap();
+            anchors
+                .iter()
+                .map(|a| (a.kind, a.span.start, a.span.end))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds(r"^\bword\b$"),
+            [
+                (AnchorKind::Start, 0, 1),
+                (AnchorKind::WordBoundary, 1, 3),
+                (AnchorKind::WordBoundary, 7, 9),
+                (AnchorKind::End, 9, 10),
+            ]
+        );
+        assert_eq!(
+            kinds(r"\A(?:\<é\B|\b{end-half})\z"),
+            [
+                (AnchorKind::TextStart, 0, 2),
+                (AnchorKind::WordStart, 5, 7),
+                (AnchorKind::NotWordBoundary, 9, 11),
+                (AnchorKind::WordEndHalf, 12, 24),
+                (AnchorKind::TextEnd, 25, 27),
+            ]
+        );
+        assert!(kinds("[$^]").is_empty());
+    }
+
+    fn bounds(pattern: &str, max_product: u64) -> Vec<(u64, usize, usize, usize, usize)> {
+        let exceeded = check_repetition_bounds(pattern, max_product).unwrap();
+        exceeded
+            .iter()
+            .map(|b| {
+                let s = &b.span;
+                (b.product, s.start, s.end, s.start_utf16, s.end_utf16)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_repetition_bounds_reports_where_the_limit_is_crossed() {
+        assert_eq!(bounds("(a{100}){100}", 1000), [(10000, 0, 13, 0, 13)]);
+        // One entry for the whole nest, spanning its outermost repetition.
+        assert_eq!(
+            bounds("é((a{10}){100}){100}", 1000),
+            [(100000, 2, 21, 1, 20)]
+        );
+        assert_eq!(bounds("(a{2,}){600}", 1000), [(1200, 0, 12, 0, 12)]);
+        assert_eq!(
+            bounds("(a{10}){100}|(b{100}){11}", 1000),
+            [(1100, 13, 25, 13, 25)]
+        );
+    }
+
+    #[test]
+    fn check_repetition_bounds_counts_unbounded_by_their_minimum() {
+        assert!(bounds("(a{10}){100}", 1000).is_empty());
+        assert!(bounds("(a*){1000}", 1000).is_empty());
+        assert_eq!(bounds("(a{0,}){5000}", 1000), [(5000, 0, 13, 0, 13)]);
+    }
+
+    fn tokens(pattern: &str) -> Vec<(TokenKind, usize, usize)> {
+        let tokens = tokenize(pattern).unwrap();
+        let span = |t: &TokenSer| (t.kind, t.span.start_utf16, t.span.end_utf16);
+        tokens.iter().map(span).collect()
+    }
+
+    #[test]
+    fn tokenize_lists_groups_before_their_contents() {
+        use TokenKind::*;
+        assert_eq!(
+            tokens(r"é(a|\.)+[bc]^"),
+            [
+                (Literal, 0, 1),
+                (Group, 1, 7),
+                (Literal, 2, 3),
+                (Alternation, 3, 4),
+                (Escape, 4, 6),
+                (Quantifier, 7, 8),
+                (Class, 8, 12),
+                (Anchor, 12, 13),
+            ]
+        );
+        assert_eq!(
+            tokens(r"((?i)\x41)"),
+            [(Group, 0, 10), (Flags, 1, 5), (Escape, 5, 9)]
+        );
+        assert_eq!(
+            tokens("((a))"),
+            [(Group, 0, 5), (Group, 1, 4), (Literal, 2, 3)]
+        );
+    }
+
+    #[test]
+    fn tokenize_finds_the_pipes_around_empty_branches() {
+        use TokenKind::*;
+        assert_eq!(
+            tokens("a||b"),
+            [
+                (Literal, 0, 1),
+                (Alternation, 1, 2),
+                (Alternation, 2, 3),
+                (Literal, 3, 4)
+            ]
+        );
+        assert_eq!(
+            tokens("|(a|)"),
+            [
+                (Alternation, 0, 1),
+                (Group, 1, 5),
+                (Literal, 2, 3),
+                (Alternation, 3, 4)
+            ]
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use regex::bytes::Regex;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::error::{Field, FieldError};
+use crate::find::{captures_iter, content, find, MatchSer};
+use crate::flags::Flags;
+use crate::utf16::Utf16Cursor;
+use crate::{compile, to_js, Error};
+
+/// The outcome for one pattern of a batch: `error` is set instead of `count` if the pattern
+/// failed to compile.
+#[derive(Debug, Serialize)]
+pub struct CountSer {
+    pub count: Option<usize>,
+    pub error: Option<Error>,
+}
+
+pub(crate) fn count_multi(text: &str, patterns: &[String], flags: &Flags) -> Vec<CountSer> {
+    let flags = Flags {
+        global: true,
+        ..*flags
+    };
+    patterns
+        .iter()
+        .map(|pattern| match compile(pattern, &flags) {
+            Ok(re) => CountSer {
+                count: Some(captures_iter(&re, text, 0, &flags).count()),
+                error: None,
+            },
+            Err(e) => CountSer {
+                count: None,
+                error: Some(e),
+            },
+        })
+        .collect()
+}
+
+/// Counts every match of each of `patterns` in `text`, with or without the `g` flag. Returns
+/// one [`CountSer`] per pattern, in order, so a pattern that fails to compile doesn't fail the
+/// others.
+#[wasm_bindgen]
+pub fn re_count_multi(text: &str, patterns: Vec<String>, flags: &str) -> JsValue {
+    to_js(&count_multi(text, &patterns, &Flags::parse(flags)))
+}
+
+/// Whether the pattern at `index` of a batch compiles, with `error` set if not.
+#[derive(Debug, Serialize)]
+pub struct ValidationSer {
+    pub index: usize,
+    pub valid: bool,
+    pub error: Option<Error>,
+}
+
+pub(crate) fn validate_batch(patterns: &[String], flags: &Flags) -> Vec<ValidationSer> {
+    patterns
+        .iter()
+        .enumerate()
+        .map(|(index, pattern)| {
+            let error = compile(pattern, flags).err();
+            ValidationSer {
+                index,
+                valid: error.is_none(),
+                error,
+            }
+        })
+        .collect()
+}
+
+/// Compiles each of `patterns`, returning one [`ValidationSer`] per pattern, in order, so a
+/// list of saved rules can show which still compile. A pattern that fails doesn't stop the
+/// others from being checked.
+#[wasm_bindgen]
+pub fn re_validate_batch(patterns: Vec<String>, flags: &str) -> JsValue {
+    to_js(&validate_batch(&patterns, &Flags::parse(flags)))
+}
+
+/// [`find`] over each of `lines`, with one result per line even if it has no match.
+pub(crate) fn find_per_line<'a>(
+    re: &'a Regex,
+    lines: &'a [String],
+    flags: &Flags,
+) -> Vec<MatchSer<'a>> {
+    lines.iter().map(|line| find(re, line, flags)).collect()
+}
+
+/// Like `re_find` over each of `lines` with the pattern compiled once, returning one result per
+/// line, in order, with offsets relative to that line. Lines without a match get an empty
+/// `matches` so the results stay aligned with the input.
+#[wasm_bindgen]
+pub fn re_find_per_line(lines: Vec<String>, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&find_per_line(&re, &lines, &flags)))
+}
+
+/// An input matched by the first pattern of [`re_subset_over_inputs`] but not the second.
+#[derive(Debug, Serialize)]
+pub struct CounterexampleSer<'a> {
+    /// The index of the input in `inputs`.
+    pub index: usize,
+    pub input: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubsetSer<'a> {
+    pub subset: bool,
+    pub counterexample: Option<CounterexampleSer<'a>>,
+}
+
+pub(crate) fn subset_over_inputs<'a>(
+    re_a: &Regex,
+    re_b: &Regex,
+    inputs: &'a [String],
+) -> SubsetSer<'a> {
+    let counterexample = inputs
+        .iter()
+        .enumerate()
+        .find(|(_, input)| re_a.is_match(input.as_bytes()) && !re_b.is_match(input.as_bytes()))
+        .map(|(index, input)| CounterexampleSer { index, input });
+    SubsetSer {
+        subset: counterexample.is_none(),
+        counterexample,
+    }
+}
+
+/// Tests whether every one of `inputs` that `pattern_a` matches somewhere is also matched by
+/// `pattern_b`, to tell if rule A is redundant given rule B. Returns `{ subset, counterexample }`
+/// with the first input, as `{ index, input }`, that only `pattern_a` matches. This is a
+/// differential test over the given inputs, not a proof that one pattern's language contains the
+/// other's. Compile errors are the usual error objects with an added `field` of `"pattern_a"` or
+/// `"pattern_b"`.
+#[wasm_bindgen]
+pub fn re_subset_over_inputs(
+    pattern_a: &str,
+    pattern_b: &str,
+    inputs: Vec<String>,
+    flags: &str,
+) -> Result<JsValue, FieldError> {
+    let flags = Flags::parse(flags);
+    let compile_field =
+        |pattern, field| compile(pattern, &flags).map_err(|error| FieldError { field, error });
+    let re_a = compile_field(pattern_a, Field::PatternA)?;
+    let re_b = compile_field(pattern_b, Field::PatternB)?;
+    Ok(to_js(&subset_over_inputs(&re_a, &re_b, &inputs)))
+}
+
+/// The span of a match in [`re_match_diff`].
+#[derive(Debug, Serialize)]
+pub struct MatchRangeSer<'a> {
+    pub content: Cow<'a, str>,
+    pub start: usize,
+    pub end: usize,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchDiffSer<'a> {
+    pub only_a: Vec<MatchRangeSer<'a>>,
+    pub only_b: Vec<MatchRangeSer<'a>>,
+    pub both: Vec<MatchRangeSer<'a>>,
+}
+
+/// The spans of the matches of `re_a` and `re_b` in `text`, split by which of the two found
+/// them.
+pub(crate) fn match_diff<'a>(
+    re_a: &Regex,
+    re_b: &Regex,
+    text: &'a str,
+    flags: &Flags,
+) -> MatchDiffSer<'a> {
+    let ranges = |re| -> BTreeSet<_> {
+        captures_iter(re, text, 0, flags)
+            .map(|caps| caps.get(0).unwrap().range())
+            .map(|range| (range.start, range.end))
+            .collect()
+    };
+    let (a, b) = (ranges(re_a), ranges(re_b));
+    let spans = |ranges: Vec<&(usize, usize)>| {
+        let mut cursor = Utf16Cursor::new(text);
+        ranges
+            .into_iter()
+            .map(|&(start, end)| MatchRangeSer {
+                content: content(&text.as_bytes()[start..end]),
+                start,
+                end,
+                start_utf16: cursor.utf16(start),
+                end_utf16: cursor.utf16(end),
+            })
+            .collect()
+    };
+    MatchDiffSer {
+        only_a: spans(a.difference(&b).collect()),
+        only_b: spans(b.difference(&a).collect()),
+        both: spans(a.intersection(&b).collect()),
+    }
+}
+
+/// Compares the matches of `pattern_a` and `pattern_b` in `text`, for an A/B view of how
+/// editing a pattern changes its matches. Returns `{ only_a, only_b, both }`, each a list of
+/// `{ content, start, end, start_utf16, end_utf16 }` in order of start. Matches are compared by
+/// their span only, so refining `\w+` to `\w{3,}` puts the words shorter than 3 chars in
+/// `only_a` and the rest in `both`, while two matches that only overlap are each listed on their
+/// own side. Without the `g` flag only the first match of each is compared.
+/// Compile errors have a `field` of `"pattern_a"` or `"pattern_b"`.
+#[wasm_bindgen]
+pub fn re_match_diff(
+    text: &str,
+    pattern_a: &str,
+    pattern_b: &str,
+    flags: &str,
+) -> Result<JsValue, FieldError> {
+    let flags = Flags::parse(flags);
+    let compile_field =
+        |pattern, field| compile(pattern, &flags).map_err(|error| FieldError { field, error });
+    let re_a = compile_field(pattern_a, Field::PatternA)?;
+    let re_b = compile_field(pattern_b, Field::PatternB)?;
+    Ok(to_js(&match_diff(&re_a, &re_b, text, &flags)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_per_line_keeps_results_aligned() {
+        let flags = Flags::parse("g");
+        let re = compile(r"\d+", &flags).unwrap();
+        let lines = [
+            "a1".to_string(),
+            "none".to_string(),
+            String::new(),
+            "é22 3".to_string(),
+        ];
+        let results = find_per_line(&re, &lines, &flags);
+        let spans: Vec<Vec<_>> = results
+            .iter()
+            .map(|r| {
+                r.matches
+                    .iter()
+                    .map(|m| (m.groups[0].start, m.groups[0].end))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(spans, [vec![(1, 2)], vec![], vec![], vec![(2, 4), (5, 6)]]);
+        // Offsets restart at each line, in UTF-16 as well.
+        assert_eq!(results[3].matches[0].groups[0].start_utf16, Some(1));
+    }
+
+    #[test]
+    fn subset_over_inputs_finds_the_first_counterexample() {
+        let flags = Flags::default();
+        let words: Vec<String> = ["cat", "bat", "dog", "bee", "ant"]
+            .map(String::from)
+            .to_vec();
+        let a = compile("a", &flags).unwrap();
+        let ab = compile("[ab]", &flags).unwrap();
+        let subset = subset_over_inputs(&a, &ab, &words);
+        assert!(subset.subset && subset.counterexample.is_none());
+
+        let t = compile("t", &flags).unwrap();
+        let subset = subset_over_inputs(&ab, &t, &words);
+        assert!(!subset.subset);
+        let counterexample = subset.counterexample.unwrap();
+        // `dog` matches neither, and `bee` is the first with an `a` or `b` but no `t`.
+        assert_eq!((counterexample.index, counterexample.input), (3, "bee"));
+    }
+
+    #[test]
+    fn subset_over_inputs_names_the_pattern_that_failed() {
+        // Only the error path is exercised, as the success path builds a JS value.
+        let err = re_subset_over_inputs("(", "a", Vec::new(), "").unwrap_err();
+        assert_eq!(err.field, Field::PatternA);
+        let err = re_subset_over_inputs("a", "[", Vec::new(), "").unwrap_err();
+        assert_eq!(err.field, Field::PatternB);
+    }
+
+    #[test]
+    fn validate_batch_checks_every_pattern() {
+        let patterns = ["a+", "(a", "", r"x # comment )"].map(str::to_owned);
+        let checked = validate_batch(&patterns, &Flags::default());
+        let summary: Vec<_> = checked.iter().map(|v| (v.index, v.valid)).collect();
+        assert_eq!(summary, [(0, true), (1, false), (2, true), (3, false)]);
+        assert!(matches!(checked[1].error, Some(Error::Syntax(_))));
+        assert!(checked[0].error.is_none());
+
+        let checked = validate_batch(&patterns, &Flags::parse("x"));
+        assert!(checked[3].valid);
+    }
+
+    #[test]
+    fn match_diff_splits_a_refined_pattern() {
+        let flags = Flags::parse("g");
+        let re_a = compile(r"\w+", &flags).unwrap();
+        let re_b = compile(r"\w{3,}", &flags).unwrap();
+        let diff = match_diff(&re_a, &re_b, "é to the sea", &flags);
+        let spans = |ranges: &[MatchRangeSer]| -> Vec<(String, usize, usize)> {
+            let span = |r: &MatchRangeSer| (r.content.to_string(), r.start, r.start_utf16);
+            ranges.iter().map(span).collect()
+        };
+        assert_eq!(spans(&diff.only_a), [("to".into(), 3, 2)]);
+        assert!(diff.only_b.is_empty());
+        assert_eq!(
+            spans(&diff.both),
+            [("the".into(), 6, 5), ("sea".into(), 10, 9)]
+        );
+    }
+
+    #[test]
+    fn match_diff_keeps_overlapping_matches_apart() {
+        let flags = Flags::parse("g");
+        let re_a = compile("ab", &flags).unwrap();
+        let re_b = compile("bc", &flags).unwrap();
+        let diff = match_diff(&re_a, &re_b, "abc", &flags);
+        assert_eq!((diff.only_a[0].start, diff.only_a[0].end), (0, 2));
+        assert_eq!((diff.only_b[0].start, diff.only_b[0].end), (1, 3));
+        assert!(diff.both.is_empty());
+        let err = re_match_diff("", "a", "(", "").unwrap_err();
+        assert_eq!(err.field, Field::PatternB);
+    }
+}
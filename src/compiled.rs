@@ -0,0 +1,288 @@
+use std::borrow::Cow;
+
+use js_sys::{Date, Function, Reflect};
+use regex::bytes::{CaptureLocations, Regex};
+use regex_automata::nfa::thompson;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::analysis::ascii_word;
+use crate::find::find;
+use crate::flags::Flags;
+use crate::{compile, compile_builder, to_js, Error};
+
+/// A pattern compiled once to be run against many inputs.
+#[wasm_bindgen]
+pub struct CompiledRegex {
+    re: Regex,
+    flags: Flags,
+    locations: CaptureLocations,
+}
+
+#[wasm_bindgen]
+impl CompiledRegex {
+    #[wasm_bindgen(constructor)]
+    pub fn new(pattern: &str, flags: &str) -> Result<CompiledRegex, Error> {
+        let flags = Flags::parse(flags);
+        let re = compile(pattern, &flags)?;
+        let locations = re.capture_locations();
+        Ok(CompiledRegex {
+            re,
+            flags,
+            locations,
+        })
+    }
+
+    /// The number of groups in the pattern, including group 0 for the whole match.
+    #[wasm_bindgen(getter)]
+    pub fn captures_len(&self) -> usize {
+        self.re.captures_len()
+    }
+
+    /// Same as [`crate::find::re_find`] with the flags given at construction.
+    pub fn find(&self, text: &str) -> JsValue {
+        to_js(&find(&self.re, text, &self.flags))
+    }
+
+    /// Searches `text` from `start_byte` for a single match without allocating per match, for
+    /// tight loops over many inputs. On a match, writes the start and end byte offsets of every
+    /// group into `locations_out` as `[start0, end0, start1, end1, ...]`, -1 for groups that did
+    /// not participate, and returns true. `locations_out` must hold `2 * captures_len` values,
+    /// and `start_byte` must be on a char boundary of `text`.
+    pub fn captures_into(
+        &mut self,
+        text: &str,
+        start_byte: usize,
+        locations_out: &mut [i32],
+    ) -> Result<bool, Error> {
+        let required = 2 * self.re.captures_len();
+        if locations_out.len() < required {
+            return Err(Error::BufferTooSmall { required });
+        }
+        if !text.is_char_boundary(start_byte) {
+            return Err(Error::InvalidRange {
+                start: start_byte,
+                end: text.len(),
+            });
+        }
+        let found = self
+            .re
+            .captures_read_at(&mut self.locations, text.as_bytes(), start_byte)
+            .is_some();
+        if found {
+            for (group, out) in locations_out[..required].chunks_exact_mut(2).enumerate() {
+                let (start, end) = match self.locations.get(group) {
+                    Some((start, end)) => match (i32::try_from(start), i32::try_from(end)) {
+                        (Ok(start_i32), Ok(end_i32)) => (start_i32, end_i32),
+                        _ => return Err(Error::InvalidRange { start, end }),
+                    },
+                    None => (-1, -1),
+                };
+                out[0] = start;
+                out[1] = end;
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// The smallest size limit [`compiled_size`] tries, 1 KiB.
+const MIN_SIZE_LIMIT: usize = 1 << 10;
+/// The largest size limit [`compiled_size`] tries, 256 MiB.
+const MAX_SIZE_LIMIT: usize = 256 << 20;
+/// [`compiled_size`] stops bisecting once it is within `1 / 2^SIZE_PRECISION_BITS` of its result.
+const SIZE_PRECISION_BITS: u32 = 6;
+
+/// About the smallest `size_limit` that `pattern` compiles under, found by doubling the limit
+/// until it fits and then bisecting the last doubling, rounding up by at most 1/64.
+pub(crate) fn compiled_size(pattern: &str, flags: &Flags) -> Result<usize, Error> {
+    let mut builder = compile_builder(pattern, flags)?;
+    let mut high = MIN_SIZE_LIMIT;
+    loop {
+        match builder.size_limit(high).build() {
+            Ok(_) => break,
+            Err(regex::Error::CompiledTooBig(_)) if high < MAX_SIZE_LIMIT => high *= 2,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let mut low = match high {
+        MIN_SIZE_LIMIT => 0,
+        _ => high / 2 + 1,
+    };
+    let tolerance = (high >> SIZE_PRECISION_BITS).max(1);
+    while high - low > tolerance {
+        let mid = low + (high - low) / 2;
+        if builder.size_limit(mid).build().is_ok() {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Ok(high)
+}
+
+/// Returns an approximation of the memory a compiled `pattern` takes, in bytes, to size caches
+/// of [`CompiledRegex`]. This is about the smallest `size_limit` the regex crate accepts for the
+/// pattern, rounded up by at most 1/64, which bounds its compiled program but not the caches
+/// that grow while searching, so it is only good for comparing patterns. Patterns over 256 MiB
+/// fail with `Error::CompiledTooBig`. Compiles the pattern once per doubling of the size from
+/// 1 KiB, plus 6 more times.
+#[wasm_bindgen]
+pub fn re_compiled_size(pattern: &str, flags: &str) -> Result<usize, Error> {
+    compiled_size(pattern, &Flags::parse(flags))
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompileProfileSer {
+    pub compile_us: u64,
+    pub approx_size_bytes: usize,
+    pub num_states_hint: Option<usize>,
+}
+
+/// The number of states of a Thompson NFA for `pattern`, built apart from the one the regex
+/// crate builds, or `None` if it fails to build.
+fn nfa_states(pattern: &str, flags: &Flags) -> Option<usize> {
+    let pattern = match flags.ascii_word {
+        true => Cow::Owned(ascii_word(pattern, flags).ok()?),
+        false => Cow::Borrowed(pattern),
+    };
+    let hir = flags.parser().parse(&pattern).ok()?;
+    let nfa = thompson::Compiler::new().build_from_hir(&hir).ok()?;
+    Some(nfa.states().len())
+}
+
+/// `performance.now` along with the global `performance` to call it on, if the host has one.
+fn performance_now() -> Option<(Function, JsValue)> {
+    let performance = Reflect::get(&js_sys::global(), &"performance".into()).ok()?;
+    let now = Reflect::get(&performance, &"now".into()).ok()?;
+    Some((now.dyn_into().ok()?, performance))
+}
+
+/// A clock in milliseconds reading `performance.now()`, or `Date.now()` where there is none.
+fn clock() -> impl Fn() -> f64 {
+    let performance = performance_now();
+    move || {
+        let now = performance.as_ref();
+        let ms = now.and_then(|(now, performance)| now.call0(performance).ok()?.as_f64());
+        ms.unwrap_or_else(Date::now)
+    }
+}
+
+/// Returns `{ compile_us, approx_size_bytes, num_states_hint }` for `pattern` without matching
+/// anything, to see why a pattern is slow to build. `compile_us` times a single build of the
+/// regex with `performance.now()`, whose precision depends on the host: browsers coarsen it to
+/// between 5 µs and 100 µs, and it falls back to the milliseconds of `Date.now()` where there is
+/// no global `performance`.
+/// `approx_size_bytes` is the estimate of [`re_compiled_size`] and
+/// `num_states_hint` the number of states of a Thompson NFA for the pattern, or `null` if it
+/// could not be built within the default limits. All three are approximate and vary between
+/// platforms and versions of the regex crate.
+#[wasm_bindgen]
+pub fn re_compile_profile(pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let builder = compile_builder(pattern, &flags)?;
+    let now = clock();
+    let start = now();
+    builder.build()?;
+    let compile_us = ((now() - start) * 1000.0) as u64;
+    Ok(to_js(&CompileProfileSer {
+        compile_us,
+        approx_size_bytes: compiled_size(pattern, &flags)?,
+        num_states_hint: nfa_states(pattern, &flags),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn captures_into_writes_group_offsets() {
+        let mut re = CompiledRegex::new(r"(a)(x)?(b)", "").unwrap();
+        let mut out = [0; 8];
+        assert!(re.captures_into("zzab", 0, &mut out).unwrap());
+        assert_eq!(out, [2, 4, 2, 3, -1, -1, 3, 4]);
+        assert!(!re.captures_into("zzab", 3, &mut out).unwrap());
+    }
+
+    #[test]
+    fn captures_into_rejects_bad_input() {
+        let mut re = CompiledRegex::new("a", "").unwrap();
+        let mut out = [0; 2];
+        assert!(matches!(
+            re.captures_into("abc", 10, &mut out),
+            Err(Error::InvalidRange { start: 10, end: 3 })
+        ));
+        assert!(matches!(
+            re.captures_into("é", 1, &mut out),
+            Err(Error::InvalidRange { start: 1, end: 2 })
+        ));
+        assert!(re.captures_into("abc", 3, &mut out).is_ok());
+        assert!(matches!(
+            re.captures_into("abc", 0, &mut [0; 1]),
+            Err(Error::BufferTooSmall { required: 2 })
+        ));
+    }
+
+    /// Compares `captures_into` with the `find` path over many short inputs. Run with
+    /// `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_captures_into_against_find() {
+        let inputs: Vec<_> = (0..100_000)
+            .map(|i| format!("user{i}@example{}.com", i % 7))
+            .collect();
+        let pattern = r"(\w+)@(\w+)\.com";
+        let mut compiled = CompiledRegex::new(pattern, "").unwrap();
+        let mut out = vec![0; 2 * compiled.captures_len()];
+
+        let start = Instant::now();
+        let mut found = 0;
+        for input in &inputs {
+            found += usize::from(compiled.captures_into(input, 0, &mut out).unwrap());
+        }
+        let captures_into = start.elapsed();
+
+        let flags = Flags::default();
+        let re = compile(pattern, &flags).unwrap();
+        let start = Instant::now();
+        let mut found_by_find = 0;
+        for input in &inputs {
+            found_by_find += find(&re, input, &flags).matches.len();
+        }
+        let naive = start.elapsed();
+
+        assert_eq!(found, found_by_find);
+        println!("captures_into: {captures_into:?}, find: {naive:?}");
+    }
+
+    #[test]
+    fn compiled_size_grows_with_the_pattern() {
+        let flags = Flags::default();
+        let literal = compiled_size("abc", &flags).unwrap();
+        let alternation = (0..200)
+            .map(|i| format!("word{i}"))
+            .collect::<Vec<_>>()
+            .join("|");
+        assert!(compiled_size(&alternation, &flags).unwrap() > literal);
+        assert!(nfa_states(&alternation, &flags).unwrap() > nfa_states("abc", &flags).unwrap());
+    }
+
+    #[test]
+    fn compiled_size_is_within_a_64th_of_the_smallest_limit() {
+        let flags = Flags::default();
+        let pattern = r"\w+@\w+\.(com|org|net)";
+        let size = compiled_size(pattern, &flags).unwrap();
+        let mut builder = compile_builder(pattern, &flags).unwrap();
+        assert!(builder.size_limit(size).build().is_ok());
+        assert!(builder.size_limit(size - size / 64 - 1).build().is_err());
+    }
+
+    #[test]
+    fn compiled_size_reports_syntax_errors() {
+        let err = compiled_size("(a", &Flags::default()).unwrap_err();
+        assert!(matches!(err, Error::Syntax(_)), "{err:?}");
+    }
+}
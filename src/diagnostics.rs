@@ -0,0 +1,356 @@
+use std::cmp::Reverse;
+
+use regex::bytes::Regex;
+
+use regex_syntax::ast::ErrorKind;
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+use regex_syntax::hir::Hir;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::find::{captures_iter, content};
+use crate::flags::Flags;
+use crate::utf16::{utf16_index_bytes, Utf16Cursor};
+use crate::{compile, to_js, Error};
+
+#[derive(Debug, Serialize)]
+pub struct ProgressSer {
+    /// Whether the whole pattern matches somewhere in the input.
+    pub matched: bool,
+    /// UTF-16 length of the longest prefix of the pattern that matches.
+    pub pattern_end_utf16: usize,
+    /// UTF-16 span in the input of the longest match of that prefix, the earliest one on ties.
+    pub text_start_utf16: usize,
+    pub text_end_utf16: usize,
+}
+
+/// `prefix`, with enough `)` appended to close the groups it leaves open, if that's all it
+/// takes to make it parse.
+fn close_groups(prefix: &str, flags: &Flags) -> Option<String> {
+    let mut candidate = prefix.to_string();
+    loop {
+        match flags.parser().parse(&candidate) {
+            Ok(_) => return Some(candidate),
+            Err(regex_syntax::Error::Parse(e)) if *e.kind() == ErrorKind::GroupUnclosed => {
+                candidate.push(')')
+            }
+            Err(_) => return None,
+        }
+    }
+}
+
+pub(crate) fn match_progress(text: &str, pattern: &str, flags: &Flags) -> ProgressSer {
+    let mut prefix_ends: Vec<_> = pattern.char_indices().map(|(i, _)| i).collect();
+    prefix_ends.push(pattern.len());
+    for &prefix_end in prefix_ends.iter().rev() {
+        let Some(candidate) = close_groups(&pattern[..prefix_end], flags) else {
+            continue;
+        };
+        let Ok(re) = compile(&candidate, flags) else {
+            continue;
+        };
+        let longest = re
+            .find_iter(text.as_bytes())
+            .max_by_key(|m| (m.len(), Reverse(m.start())));
+        if let Some(m) = longest {
+            let mut cursor = Utf16Cursor::new(text);
+            return ProgressSer {
+                matched: prefix_end == pattern.len(),
+                pattern_end_utf16: utf16_index_bytes(pattern, prefix_end),
+                text_start_utf16: cursor.utf16(m.start()),
+                text_end_utf16: cursor.utf16(m.end()),
+            };
+        }
+    }
+    // Only reachable if even the empty pattern failed to compile.
+    ProgressSer {
+        matched: false,
+        pattern_end_utf16: 0,
+        text_start_utf16: 0,
+        text_end_utf16: 0,
+    }
+}
+
+/// Reports how far `pattern` gets against `text`, to hint learners at where a pattern stops
+/// matching. This is a heuristic: it tries ever shorter prefixes of the pattern, closing any
+/// groups they leave open and skipping those that still don't parse, and reports the first
+/// prefix that matches along with the span of its longest match in the input.
+#[wasm_bindgen]
+pub fn re_match_progress(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    compile(pattern, &flags)?;
+    Ok(to_js(&match_progress(text, pattern, &flags)))
+}
+
+/// The literals that every match of `hir` starts with one of, or `None` if a match could start
+/// with anything.
+fn prefixes(hir: &Hir) -> Option<Vec<Vec<u8>>> {
+    let seq = Extractor::new().extract(hir);
+    let literals = seq.literals()?;
+    if literals.iter().any(|literal| literal.is_empty()) {
+        return None;
+    }
+    Some(literals.iter().map(|l| l.as_bytes().to_vec()).collect())
+}
+
+/// The longest literal that every match of `hir` ends with, if it is not empty.
+pub(crate) fn required_suffix(hir: &Hir) -> Option<String> {
+    let seq = Extractor::new().kind(ExtractKind::Suffix).extract(hir);
+    let suffix = seq.longest_common_suffix()?;
+    (!suffix.is_empty()).then(|| content(suffix).into_owned())
+}
+
+pub(crate) fn candidate_starts(text: &str, hir: &Hir) -> Vec<usize> {
+    let bytes = text.as_bytes();
+    let mut cursor = Utf16Cursor::new(text);
+    let mut starts: Vec<usize> = match prefixes(hir) {
+        Some(prefixes) => (0..bytes.len())
+            .filter(|&i| prefixes.iter().any(|prefix| bytes[i..].starts_with(prefix)))
+            .map(|i| cursor.utf16(i))
+            .collect(),
+        None => text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([text.len()])
+            .map(|i| cursor.utf16(i))
+            .collect(),
+    };
+    // Byte literals without the u flag may start inside a char, at the same UTF-16 offset.
+    starts.dedup();
+    starts
+}
+
+/// Returns the UTF-16 offsets of `text` where a match of `pattern` could begin judging by the
+/// literals every match starts with, such as `get` and `set` for `(get|set)\w+`, to show why
+/// the engine can skip most of the input. The regex crate's own prefilter is not exposed, so
+/// this approximates it with the prefix literals regex-syntax extracts, and is only advisory:
+/// the engine may pick other literals or none, and most candidates may fail to match. Look-around
+/// assertions like `^` are ignored. A pattern without such literals, like `\w+`, could begin at
+/// every position, and all of them are returned.
+#[wasm_bindgen]
+pub fn re_candidate_starts(text: &str, pattern: &str, flags: &str) -> Result<Vec<usize>, Error> {
+    let flags = Flags::parse(flags);
+    let hir = flags.parser().parse(pattern)?;
+    compile(pattern, &flags)?;
+    Ok(candidate_starts(text, &hir))
+}
+
+/// Returns the longest literal that every match of `pattern` ends with, or `null` if there is
+/// none, for filtering inputs by suffix before running the pattern: `.*\.rs$` gives `.rs` and
+/// `(foo|bar)\.txt` gives `.txt`. Found with regex-syntax's suffix literal extraction, which
+/// gives up on large classes and alternations, so a suffix may go unreported. Bytes that are not
+/// valid UTF-8 are escaped as `\x..`, like match content.
+#[wasm_bindgen]
+pub fn re_required_suffix(pattern: &str, flags: &str) -> Result<Option<String>, Error> {
+    let flags = Flags::parse(flags);
+    let hir = flags.parser().parse(pattern)?;
+    compile(pattern, &flags)?;
+    Ok(required_suffix(&hir))
+}
+
+/// The input [`re_empty_behavior`] scans: a word, a digit, a space and a line break, so that
+/// anchors and word boundaries have somewhere to match.
+const EMPTY_SAMPLE: &str = "ab 1\nb";
+
+#[derive(Debug, Serialize)]
+pub struct EmptyBehaviorSer {
+    /// Whether the pattern matches the empty input.
+    pub matches_empty: bool,
+    pub sample: &'static str,
+    /// The offsets of the empty matches of a global scan of `sample`, which is ASCII so UTF-8
+    /// and UTF-16 offsets agree.
+    pub example_positions: Vec<usize>,
+}
+
+pub(crate) fn empty_behavior(re: &Regex, flags: &Flags) -> EmptyBehaviorSer {
+    let flags = Flags {
+        global: true,
+        ..*flags
+    };
+    EmptyBehaviorSer {
+        matches_empty: re.is_match(b""),
+        sample: EMPTY_SAMPLE,
+        example_positions: captures_iter(re, EMPTY_SAMPLE, 0, &flags)
+            .map(|caps| caps.get(0).unwrap())
+            .filter(|m| m.is_empty())
+            .map(|m| m.start())
+            .collect(),
+    }
+}
+
+/// Returns `{ matches_empty, sample, example_positions }` to explain surprising results of
+/// patterns that can match the empty string, like `a*` matching between every char of `bb`.
+/// `matches_empty` tells whether the pattern matches the empty input, and `example_positions`
+/// lists where a global scan of the fixed `sample`, `"ab 1\nb"`, finds empty matches. An empty
+/// list means every match of the sample consumed input, not that the pattern can't match empty.
+#[wasm_bindgen]
+pub fn re_empty_behavior(pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&empty_behavior(&re, &flags)))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShrunkSer<'a> {
+    pub content: &'a str,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+}
+
+/// Trims chunks off either end of `text` as long as what is left still matches, halving the
+/// chunk whenever neither end can lose one, down to a single char.
+pub(crate) fn shrink_input<'a>(re: &Regex, text: &'a str) -> Option<ShrunkSer<'a>> {
+    let mut bounds: Vec<_> = text.char_indices().map(|(i, _)| i).collect();
+    bounds.push(text.len());
+    let matches =
+        |start: usize, end: usize| re.is_match(&text.as_bytes()[bounds[start]..bounds[end]]);
+    let (mut start, mut end) = (0, bounds.len() - 1);
+    if !matches(start, end) {
+        return None;
+    }
+    let mut chunk = end.div_ceil(2).max(1);
+    loop {
+        let mut shrunk = false;
+        if start + chunk <= end && matches(start + chunk, end) {
+            start += chunk;
+            shrunk = true;
+        }
+        if start + chunk <= end && matches(start, end - chunk) {
+            end -= chunk;
+            shrunk = true;
+        }
+        if !shrunk {
+            if chunk == 1 {
+                break;
+            }
+            chunk /= 2;
+        }
+    }
+    let mut cursor = Utf16Cursor::new(text);
+    Some(ShrunkSer {
+        content: &text[bounds[start]..bounds[end]],
+        start_utf16: cursor.utf16(bounds[start]),
+        end_utf16: cursor.utf16(bounds[end]),
+    })
+}
+
+/// Returns `{ content, start_utf16, end_utf16 }` for a short substring of `text` that `pattern`
+/// still matches on its own, or `null` if it doesn't match `text`, to show what minimal input
+/// triggers an over-broad pattern: `\d+` in `abc123def` shrinks to a single digit. A heuristic
+/// minimizer in the spirit of delta debugging: it trims ever smaller chunks off both ends, so
+/// the result is usually but not always the smallest. Anchors and `\b` see the substring as
+/// the whole input.
+#[wasm_bindgen]
+pub fn re_shrink_input(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&shrink_input(&re, text)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(text: &str, pattern: &str) -> (bool, usize, usize, usize) {
+        let p = match_progress(text, pattern, &Flags::default());
+        (
+            p.matched,
+            p.pattern_end_utf16,
+            p.text_start_utf16,
+            p.text_end_utf16,
+        )
+    }
+
+    #[test]
+    fn match_progress_reports_a_full_match() {
+        assert_eq!(progress("x12-34", r"\d+-\d+"), (true, 7, 1, 6));
+    }
+
+    #[test]
+    fn match_progress_stops_at_the_longest_matching_prefix() {
+        assert_eq!(progress("12-34y", r"\d+-\d+x"), (false, 7, 0, 5));
+    }
+
+    #[test]
+    fn match_progress_closes_open_groups() {
+        // `(ab)(c` is tried as `(ab)(c)`.
+        assert_eq!(progress("abce", "(ab)(cd)"), (false, 6, 0, 3));
+    }
+
+    fn candidates(text: &str, pattern: &str) -> Vec<usize> {
+        candidate_starts(text, &Flags::parse("u").parser().parse(pattern).unwrap())
+    }
+
+    #[test]
+    fn candidate_starts_follow_prefix_literals() {
+        // Every `get` and `set` is a candidate, whether or not a `\d` follows.
+        assert_eq!(
+            candidates("get setx é getter set1", r"(get|set)\d"),
+            [0, 4, 11, 18]
+        );
+        assert_eq!(candidates("abab", "ab"), [0, 2]);
+    }
+
+    #[test]
+    fn candidate_starts_without_literals_are_every_position() {
+        assert_eq!(candidates("é😀", r"\w+"), [0, 1, 3]);
+        // An empty alternative means a match can start anywhere.
+        assert_eq!(candidates("ab", "x|"), [0, 1, 2]);
+    }
+
+    fn behavior(pattern: &str, flags: &str) -> (bool, Vec<usize>) {
+        let flags = Flags::parse(flags);
+        let behavior = empty_behavior(&compile(pattern, &flags).unwrap(), &flags);
+        (behavior.matches_empty, behavior.example_positions)
+    }
+
+    #[test]
+    fn empty_behavior_lists_zero_width_matches_of_the_sample() {
+        // On "ab 1\nb", `a*` consumes the `a`, then matches empty at every later position but
+        // the one right after it.
+        assert_eq!(behavior("a*", ""), (true, vec![2, 3, 4, 5, 6]));
+        assert_eq!(behavior(r"\b", ""), (false, vec![0, 2, 3, 4, 5, 6]));
+        assert_eq!(behavior("^", "m"), (true, vec![0, 5]));
+        assert_eq!(behavior("b+", ""), (false, vec![]));
+    }
+
+    fn shrunk(pattern: &str, text: &str) -> Option<(String, usize, usize)> {
+        let re = compile(pattern, &Flags::default()).unwrap();
+        let shrunk = shrink_input(&re, text)?;
+        Some((
+            shrunk.content.to_string(),
+            shrunk.start_utf16,
+            shrunk.end_utf16,
+        ))
+    }
+
+    #[test]
+    fn shrink_input_keeps_a_minimal_match() {
+        let (content, start, end) = shrunk(r"\d+", "abc123def").unwrap();
+        assert_eq!(content.len(), 1);
+        assert!(("1".."4").contains(&content.as_str()));
+        assert_eq!(end - start, 1);
+        assert_eq!(shrunk("é+😀", "xéé😀y"), Some(("é😀".to_string(), 2, 5)));
+        assert_eq!(shrunk("x", "abc"), None);
+    }
+
+    #[test]
+    fn shrink_input_can_empty_the_input() {
+        assert_eq!(shrunk("a*", "bab"), Some((String::new(), 3, 3)));
+        assert_eq!(shrunk("", ""), Some((String::new(), 0, 0)));
+    }
+
+    fn suffix(pattern: &str, flags: &str) -> Option<String> {
+        required_suffix(&Flags::parse(flags).parser().parse(pattern).unwrap())
+    }
+
+    #[test]
+    fn required_suffix_is_the_common_end_of_every_match() {
+        assert_eq!(suffix(r".*\.rs$", "").as_deref(), Some(".rs"));
+        assert_eq!(suffix(r"(foo|bar)\.txt", "").as_deref(), Some(".txt"));
+        assert_eq!(suffix("(?:ab|cb)", "").as_deref(), Some("b"));
+        assert_eq!(suffix(r"\w+", "u"), None);
+        assert_eq!(suffix("a|b", ""), None);
+        assert_eq!(suffix(r"x(?-u:\xFF)", "").as_deref(), Some(r"x\xFF"));
+    }
+}
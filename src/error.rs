@@ -0,0 +1,283 @@
+use regex_syntax::{ast, hir};
+use serde::Serialize;
+use wasm_bindgen::JsValue;
+
+use crate::to_js;
+use crate::utf16::utf16_index_bytes;
+
+/// Errors returned to JS, serialized as an object tagged with a `type` field.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Error {
+    /// The pattern could not be parsed or translated.
+    Syntax(ReSyntax),
+    /// The pattern compiled to a program larger than `size_limit` bytes.
+    CompiledTooBig { size_limit: usize },
+    /// The pattern was empty and the `reject_empty_pattern` option was set.
+    EmptyPattern,
+    /// An options object, or another object argument, did not have the expected shape.
+    InvalidOptions { message: String },
+    /// An output buffer passed by the caller holds fewer than `required` values.
+    BufferTooSmall { required: usize },
+    /// The pattern has no capture group with this index.
+    InvalidGroup { group: usize },
+    /// The pattern has no capture group with this name.
+    UnknownGroupName { name: String },
+    /// A type to parse values as that is not one of those supported.
+    UnknownValueType { value_type: String },
+    /// A byte range was out of bounds, reversed, not on char boundaries or too large for an i32.
+    InvalidRange { start: usize, end: usize },
+    /// A `$` reference of a replacement names a group the pattern doesn't have. `start` and
+    /// `end` are the span of `reference` in the replacement.
+    InvalidReplacementReference {
+        reference: String,
+        start: usize,
+        end: usize,
+        start_utf16: usize,
+        end_utf16: usize,
+    },
+    /// Any other error reported by the regex crate.
+    Other { message: String },
+}
+
+/// Which input of a call an error is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Pattern,
+    Replacement,
+    /// The first of two patterns being compared.
+    PatternA,
+    /// The second of two patterns being compared.
+    PatternB,
+    /// A pattern that the matches of another must also match.
+    FilterPattern,
+}
+
+/// An [`Error`] along with the input that caused it, serialized as the error object with an
+/// added `field`.
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub field: Field,
+    #[serde(flatten)]
+    pub error: Error,
+}
+
+/// Which step of compiling a pattern failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// The pattern is not valid syntax.
+    Parse,
+    /// The syntax is valid but the pattern uses something the matcher doesn't support.
+    Translate,
+}
+
+/// A pattern syntax error, with the offending span of the pattern in both UTF-8 bytes and
+/// UTF-16 code units.
+#[derive(Debug, Serialize)]
+pub struct ReSyntax {
+    /// Debug rendering of the underlying error kind. Meant for humans, it may change between
+    /// versions of the regex crate.
+    pub kind: String,
+    /// Stable snake_case code for the error kind, `"other"` for kinds without a dedicated code.
+    pub kind_code: &'static str,
+    pub phase: Phase,
+    pub message: String,
+    pub start: usize,
+    pub end: usize,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+}
+
+impl ReSyntax {
+    fn new(
+        kind: String,
+        kind_code: &'static str,
+        phase: Phase,
+        message: String,
+        pattern: &str,
+        span: &ast::Span,
+    ) -> Self {
+        ReSyntax {
+            kind,
+            kind_code,
+            phase,
+            message,
+            start: span.start.offset,
+            end: span.end.offset,
+            start_utf16: utf16_index_bytes(pattern, span.start.offset),
+            end_utf16: utf16_index_bytes(pattern, span.end.offset),
+        }
+    }
+}
+
+impl From<regex_syntax::Error> for ReSyntax {
+    fn from(e: regex_syntax::Error) -> Self {
+        match e {
+            regex_syntax::Error::Parse(e) => ReSyntax::new(
+                format!("{:?}", e.kind()),
+                ast_kind_code(e.kind()),
+                Phase::Parse,
+                e.kind().to_string(),
+                e.pattern(),
+                e.span(),
+            ),
+            regex_syntax::Error::Translate(e) => ReSyntax::new(
+                format!("{:?}", e.kind()),
+                hir_kind_code(e.kind()),
+                Phase::Translate,
+                e.kind().to_string(),
+                e.pattern(),
+                e.span(),
+            ),
+            e => ReSyntax {
+                kind: format!("{:?}", e),
+                kind_code: "other",
+                phase: Phase::Parse,
+                message: e.to_string(),
+                start: 0,
+                end: 0,
+                start_utf16: 0,
+                end_utf16: 0,
+            },
+        }
+    }
+}
+
+fn ast_kind_code(kind: &ast::ErrorKind) -> &'static str {
+    use ast::ErrorKind::*;
+    match kind {
+        CaptureLimitExceeded => "capture_limit_exceeded",
+        ClassEscapeInvalid => "invalid_class_escape",
+        ClassRangeInvalid | ClassRangeLiteral => "invalid_class_range",
+        ClassUnclosed => "unclosed_class",
+        DecimalEmpty | DecimalInvalid => "invalid_decimal",
+        EscapeHexEmpty | EscapeHexInvalid | EscapeHexInvalidDigit => "invalid_hex_escape",
+        EscapeUnrecognized => "unrecognized_escape",
+        FlagDanglingNegation | FlagRepeatedNegation { .. } => "invalid_flag_negation",
+        FlagDuplicate { .. } => "duplicate_flag",
+        FlagUnrecognized => "unrecognized_flag",
+        GroupNameDuplicate { .. } => "duplicate_group_name",
+        GroupNameEmpty | GroupNameInvalid => "invalid_group_name",
+        GroupUnclosed => "unclosed_group",
+        GroupUnopened => "unopened_group",
+        NestLimitExceeded(_) => "nest_limit_exceeded",
+        RepetitionCountInvalid | RepetitionCountDecimalEmpty => "invalid_repetition_count",
+        RepetitionCountUnclosed => "unclosed_repetition",
+        RepetitionMissing => "missing_repetition_operand",
+        SpecialWordBoundaryUnclosed | SpecialWordBoundaryUnrecognized => "invalid_word_boundary",
+        UnicodeClassInvalid => "invalid_unicode_class",
+        // Valid in JS and PCRE, so worth telling apart from plain syntax errors.
+        UnsupportedBackreference => "unsupported_backreference",
+        UnsupportedLookAround => "unsupported_lookaround",
+        EscapeUnexpectedEof
+        | FlagUnexpectedEof
+        | GroupNameUnexpectedEof
+        | SpecialWordOrRepetitionUnexpectedEof => "unexpected_eof",
+        _ => "other",
+    }
+}
+
+fn hir_kind_code(kind: &hir::ErrorKind) -> &'static str {
+    use hir::ErrorKind::*;
+    match kind {
+        UnicodeNotAllowed => "unicode_not_allowed",
+        InvalidUtf8 => "invalid_utf8",
+        InvalidLineTerminator => "invalid_line_terminator",
+        UnicodePropertyNotFound | UnicodePropertyValueNotFound => "unknown_unicode_property",
+        UnicodePerlClassNotFound | UnicodeCaseUnavailable => "unicode_unavailable",
+        _ => "other",
+    }
+}
+
+impl From<ReSyntax> for Error {
+    fn from(e: ReSyntax) -> Self {
+        Error::Syntax(e)
+    }
+}
+
+impl From<regex_syntax::Error> for Error {
+    fn from(e: regex_syntax::Error) -> Self {
+        Error::Syntax(e.into())
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(e: regex::Error) -> Self {
+        match e {
+            regex::Error::CompiledTooBig(size_limit) => Error::CompiledTooBig { size_limit },
+            e => Error::Other {
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+impl From<Error> for JsValue {
+    fn from(e: Error) -> Self {
+        to_js(&e)
+    }
+}
+
+impl From<FieldError> for JsValue {
+    fn from(e: FieldError) -> Self {
+        to_js(&e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flags::Flags;
+
+    fn syntax_error(pattern: &str, flags: &str) -> ReSyntax {
+        Flags::parse(flags)
+            .parser()
+            .parse(pattern)
+            .unwrap_err()
+            .into()
+    }
+
+    #[test]
+    fn parse_errors_report_the_parse_phase() {
+        let e = syntax_error("a(b", "");
+        assert_eq!((e.kind_code, e.phase), ("unclosed_group", Phase::Parse));
+        assert_eq!((e.start, e.end), (1, 2));
+    }
+
+    #[test]
+    fn translate_errors_report_the_translate_phase() {
+        let e = syntax_error(r"\p{L}", "");
+        assert_eq!(
+            (e.kind_code, e.phase),
+            ("unicode_not_allowed", Phase::Translate)
+        );
+        let e = syntax_error(r"é\p{Nope}", "u");
+        assert_eq!(
+            (e.kind_code, e.phase),
+            ("unknown_unicode_property", Phase::Translate)
+        );
+        assert_eq!((e.start, e.end, e.start_utf16, e.end_utf16), (2, 10, 1, 9));
+    }
+
+    #[test]
+    fn look_around_has_its_own_code() {
+        let e = syntax_error("a(?=b)", "");
+        assert_eq!(
+            (e.kind_code, e.phase),
+            ("unsupported_lookaround", Phase::Parse)
+        );
+        assert_eq!((e.start, e.end), (1, 4));
+    }
+
+    #[test]
+    fn backreference_has_its_own_code() {
+        let e = syntax_error(r"(a)\1", "");
+        assert_eq!(
+            (e.kind_code, e.phase),
+            ("unsupported_backreference", Phase::Parse)
+        );
+        assert_eq!((e.start, e.end), (3, 5));
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::BTreeSet;
+
+use regex::bytes::Regex;
+use regex_syntax::hir::{Class, Hir, HirKind};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::find::content;
+use crate::flags::Flags;
+use crate::{compile, to_js, Error};
+
+/// Bytes matched by `hir`, built from the shortest alternatives, the first char of each class
+/// and the minimum number of repetitions. `None` if `hir` can't match anything, such as an empty
+/// class. Look-around assertions are skipped, so the result may not actually match.
+fn example(hir: &Hir) -> Option<Vec<u8>> {
+    Some(match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => Vec::new(),
+        HirKind::Literal(literal) => literal.0.to_vec(),
+        HirKind::Class(Class::Unicode(class)) => {
+            let c = class.ranges().first()?.start();
+            c.encode_utf8(&mut [0; 4]).as_bytes().to_vec()
+        }
+        HirKind::Class(Class::Bytes(class)) => vec![class.ranges().first()?.start()],
+        HirKind::Repetition(repetition) => {
+            if repetition.min == 0 {
+                return Some(Vec::new());
+            }
+            example(&repetition.sub)?.repeat(repetition.min as usize)
+        }
+        HirKind::Capture(capture) => example(&capture.sub)?,
+        HirKind::Concat(hirs) => {
+            let mut bytes = Vec::new();
+            for hir in hirs {
+                bytes.extend(example(hir)?);
+            }
+            bytes
+        }
+        HirKind::Alternation(hirs) => {
+            hirs.iter().filter_map(example).reduce(|shortest, bytes| {
+                if bytes.len() < shortest.len() {
+                    bytes
+                } else {
+                    shortest
+                }
+            })?
+        }
+    })
+}
+
+/// A shortest string that `re`, compiled from `hir`, matches in full, see [`re_example_match`].
+pub(crate) fn example_match(re: &Regex, hir: &Hir) -> Option<String> {
+    let bytes = example(hir)?;
+    let matched = re.find(&bytes)?;
+    (matched.len() == bytes.len()).then(|| content(&bytes).into_owned())
+}
+
+/// Returns an input that `pattern` matches, or `null` if it can't match anything. The example is
+/// one of the shortest, built from the shortest alternative (the first one on ties), the first
+/// char of each class and the minimum number of repetitions, so it is valid but arbitrary
+/// rather than canonical: `a[bc]d+` gives `abd`. `null` is also returned when assertions such
+/// as `\b` reject the example. Bytes that are not valid UTF-8 are escaped as `\x..`, like match
+/// content.
+#[wasm_bindgen]
+pub fn re_example_match(pattern: &str, flags: &str) -> Result<Option<String>, Error> {
+    let flags = Flags::parse(flags);
+    let hir = flags.parser().parse(pattern)?;
+    let re = compile(pattern, &flags)?;
+    Ok(example_match(&re, &hir))
+}
+
+/// Classes with more members than this are summarized rather than listed.
+const ALPHABET_LIST_LIMIT: usize = 256;
+
+/// A class too large to list, see [`re_alphabet`].
+#[derive(Debug, Serialize)]
+pub struct ClassSummarySer {
+    pub first: String,
+    pub last: String,
+    /// How many chars, or bytes without the `u` flag, the class has.
+    pub len: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlphabetSer {
+    pub chars: Vec<String>,
+    pub large_classes: Vec<ClassSummarySer>,
+    /// Whether some class matches any char, except possibly `\n`, like `.` does. Such classes are
+    /// always summarized.
+    pub any: bool,
+}
+
+/// The number of chars in `start..=end`, which skips the surrogate code points.
+fn char_count(start: char, end: char) -> usize {
+    let surrogates = if (start as u32) < 0xD800 && end as u32 > 0xDFFF {
+        0x800
+    } else {
+        0
+    };
+    (end as usize - start as usize + 1) - surrogates
+}
+
+#[derive(Default)]
+struct Alphabet {
+    chars: BTreeSet<char>,
+    /// Bytes that are not chars on their own, from byte literals and classes.
+    bytes: BTreeSet<u8>,
+    large_classes: Vec<ClassSummarySer>,
+    any: bool,
+}
+
+impl Alphabet {
+    fn push_byte(&mut self, byte: u8) {
+        match byte.is_ascii() {
+            true => self.chars.insert(char::from(byte)),
+            false => self.bytes.insert(byte),
+        };
+    }
+
+    fn visit(&mut self, hir: &Hir) {
+        match hir.kind() {
+            HirKind::Empty | HirKind::Look(_) => {}
+            HirKind::Literal(literal) => {
+                for chunk in literal.0.utf8_chunks() {
+                    self.chars.extend(chunk.valid().chars());
+                    for &byte in chunk.invalid() {
+                        self.push_byte(byte);
+                    }
+                }
+            }
+            HirKind::Class(Class::Unicode(class)) => {
+                let mut complement = class.clone();
+                complement.negate();
+                let any = complement
+                    .ranges()
+                    .iter()
+                    .all(|r| r.start() == '\n' && r.end() == '\n');
+                let ranges = class.ranges();
+                let len: usize = ranges.iter().map(|r| char_count(r.start(), r.end())).sum();
+                if !any && len <= ALPHABET_LIST_LIMIT {
+                    self.chars
+                        .extend(ranges.iter().flat_map(|r| r.start()..=r.end()));
+                } else if let (Some(first), Some(last)) = (ranges.first(), ranges.last()) {
+                    self.any |= any;
+                    self.large_classes.push(ClassSummarySer {
+                        first: first.start().to_string(),
+                        last: last.end().to_string(),
+                        len,
+                    });
+                }
+            }
+            HirKind::Class(Class::Bytes(class)) => {
+                let mut complement = class.clone();
+                complement.negate();
+                let any = complement
+                    .ranges()
+                    .iter()
+                    .all(|r| r.start() == b'\n' && r.end() == b'\n');
+                let ranges = class.ranges();
+                if !any {
+                    for byte in ranges.iter().flat_map(|r| r.start()..=r.end()) {
+                        self.push_byte(byte);
+                    }
+                } else if let (Some(first), Some(last)) = (ranges.first(), ranges.last()) {
+                    self.any = true;
+                    self.large_classes.push(ClassSummarySer {
+                        first: content(&[first.start()]).into_owned(),
+                        last: content(&[last.end()]).into_owned(),
+                        len: ranges.iter().map(|r| r.len()).sum(),
+                    });
+                }
+            }
+            HirKind::Repetition(repetition) => self.visit(&repetition.sub),
+            HirKind::Capture(capture) => self.visit(&capture.sub),
+            HirKind::Concat(hirs) | HirKind::Alternation(hirs) => {
+                for hir in hirs {
+                    self.visit(hir);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn alphabet(hir: &Hir) -> AlphabetSer {
+    let mut alphabet = Alphabet::default();
+    alphabet.visit(hir);
+    let bytes = alphabet.bytes.iter().map(|&b| content(&[b]).into_owned());
+    AlphabetSer {
+        chars: alphabet
+            .chars
+            .iter()
+            .map(char::to_string)
+            .chain(bytes)
+            .collect(),
+        large_classes: alphabet.large_classes,
+        any: alphabet.any,
+    }
+}
+
+/// Returns `{ chars, large_classes, any }` describing the characters `pattern` can match, for
+/// building targeted inputs. `chars` lists every member of literals and classes in code point
+/// order, each as its own string, followed by bytes that are not valid UTF-8 on their own as
+/// `\x..` escapes. Classes with more than 256 members are summarized in `large_classes` as
+/// `{ first, last, len }` instead, as are classes matching any char but possibly `\n`, like
+/// `.`, which also set `any`.
+/// Case-insensitive literals contribute every case variant.
+#[wasm_bindgen]
+pub fn re_alphabet(pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let hir = Flags::parse(flags).parser().parse(pattern)?;
+    Ok(to_js(&alphabet(&hir)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_for(pattern: &str, flags: &str) -> Option<String> {
+        let flags = Flags::parse(flags);
+        let hir = flags.parser().parse(pattern).unwrap();
+        example_match(&compile(pattern, &flags).unwrap(), &hir)
+    }
+
+    #[test]
+    fn example_match_takes_the_shortest_parts() {
+        assert_eq!(example_for(r"a[bc]d+", "").as_deref(), Some("abd"));
+        assert_eq!(
+            example_for(r"(?:long|ab|cd)x{2,5}y?", "").as_deref(),
+            Some("abxx")
+        );
+        assert_eq!(example_for(r"\d{3}-é", "u").as_deref(), Some("000-é"));
+    }
+
+    #[test]
+    fn example_match_is_none_when_nothing_matches() {
+        assert_eq!(example_for(r"a[^\s\S]", "u"), None);
+        // There is never a word boundary between `a` and `b`.
+        assert_eq!(example_for(r"a\bb", ""), None);
+    }
+
+    fn alphabet_of(pattern: &str, flags: &str) -> AlphabetSer {
+        alphabet(&Flags::parse(flags).parser().parse(pattern).unwrap())
+    }
+
+    #[test]
+    fn alphabet_lists_literals_and_small_classes() {
+        let abc = alphabet_of("[a-c]x", "");
+        assert_eq!(abc.chars, ["a", "b", "c", "x"]);
+        assert!(abc.large_classes.is_empty() && !abc.any);
+        // Case-insensitive literals contribute every case variant, the Kelvin sign included.
+        assert_eq!(
+            alphabet_of("é|k", "iu").chars,
+            ["K", "k", "É", "é", "\u{212A}"]
+        );
+    }
+
+    #[test]
+    fn alphabet_summarizes_large_classes() {
+        let letters = alphabet_of(r"\p{Greek}", "u");
+        assert!(letters.chars.is_empty() && !letters.any);
+        assert!(letters.large_classes[0].len > 256);
+        assert_eq!(letters.large_classes[0].first, "Ͱ");
+
+        let dot = alphabet_of("a.", "u");
+        assert_eq!(dot.chars, ["a"]);
+        assert!(dot.any);
+        assert_eq!(dot.large_classes[0].first, "\0");
+        assert_eq!(dot.large_classes[0].last, "\u{10FFFF}");
+    }
+}
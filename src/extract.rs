@@ -0,0 +1,270 @@
+use std::borrow::Cow;
+
+use regex::bytes::Regex;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::find::{captures_iter, content};
+use crate::flags::Flags;
+use crate::utf16::Utf16Cursor;
+use crate::{compile, to_js, Error};
+
+/// The largest integer a JS number holds exactly, `Number.MAX_SAFE_INTEGER`.
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// What [`re_extract_typed`] parses the content of a group as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    Int,
+    Float,
+    Date,
+}
+
+impl ValueType {
+    pub fn parse(value_type: &str) -> Result<ValueType, Error> {
+        match value_type {
+            "int" => Ok(ValueType::Int),
+            "float" => Ok(ValueType::Float),
+            "date" => Ok(ValueType::Date),
+            _ => Err(Error::UnknownValueType {
+                value_type: value_type.to_string(),
+            }),
+        }
+    }
+}
+
+/// Why a match was left out of [`ExtractedSer::values`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The content is not written as the type.
+    Invalid,
+    /// The content is a valid number that a JS number can't hold exactly, or at all.
+    OutOfRange,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypedMatchSer<'a> {
+    pub match_num: usize,
+    pub content: Cow<'a, str>,
+    pub value: f64,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkippedSer {
+    pub match_num: usize,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExtractedSer<'a> {
+    pub values: Vec<TypedMatchSer<'a>>,
+    pub skipped: Vec<SkippedSer>,
+}
+
+/// The days from 1970-01-01 to the given date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Milliseconds since the Unix epoch at midnight UTC of a `YYYY-MM-DD` date.
+fn parse_date(s: &str) -> Option<f64> {
+    let bytes = s.as_bytes();
+    let digits = |range: std::ops::Range<usize>| {
+        bytes[range.clone()]
+            .iter()
+            .all(u8::is_ascii_digit)
+            .then(|| s[range].parse::<i64>().unwrap())
+    };
+    if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return None;
+    }
+    let (year, month, day) = (digits(0..4)?, digits(5..7)?, digits(8..10)?);
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_len = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+    if !(1..=month_len).contains(&day) {
+        return None;
+    }
+    Some((days_from_civil(year, month, day) * 86_400_000) as f64)
+}
+
+fn parse_value(s: &str, value_type: ValueType) -> Result<f64, SkipReason> {
+    match value_type {
+        ValueType::Int => match s.parse::<i64>() {
+            Ok(n) if n.unsigned_abs() <= MAX_SAFE_INTEGER as u64 => Ok(n as f64),
+            Ok(_) => Err(SkipReason::OutOfRange),
+            // Digits only fail to parse by overflowing an `i64`.
+            Err(_) => {
+                let digits = s.strip_prefix(['+', '-']).unwrap_or(s);
+                if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+                    Err(SkipReason::OutOfRange)
+                } else {
+                    Err(SkipReason::Invalid)
+                }
+            }
+        },
+        ValueType::Float => {
+            // Rust also parses `inf` and `NaN`, which aren't numbers as written in text.
+            if !s.bytes().any(|b| b.is_ascii_digit()) {
+                return Err(SkipReason::Invalid);
+            }
+            match s.parse::<f64>() {
+                Ok(n) if n.is_finite() => Ok(n),
+                Ok(_) => Err(SkipReason::OutOfRange),
+                Err(_) => Err(SkipReason::Invalid),
+            }
+        }
+        ValueType::Date => parse_date(s).ok_or(SkipReason::Invalid),
+    }
+}
+
+pub(crate) fn extract_typed<'a>(
+    re: &Regex,
+    text: &'a str,
+    group: usize,
+    value_type: ValueType,
+    flags: &Flags,
+) -> Result<ExtractedSer<'a>, Error> {
+    if group >= re.captures_len() {
+        return Err(Error::InvalidGroup { group });
+    }
+    let mut cursor = Utf16Cursor::new(text);
+    let mut extracted = ExtractedSer {
+        values: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for (match_num, caps) in captures_iter(re, text, 0, flags).enumerate() {
+        let Some(m) = caps.get(group) else { continue };
+        let content = content(m.as_bytes());
+        match parse_value(&content, value_type) {
+            Ok(value) => extracted.values.push(TypedMatchSer {
+                match_num,
+                content,
+                value,
+                start_utf16: cursor.utf16(m.start()),
+                end_utf16: cursor.utf16(m.end()),
+            }),
+            Err(reason) => extracted.skipped.push(SkippedSer { match_num, reason }),
+        }
+    }
+    Ok(extracted)
+}
+
+/// Returns `{ values, skipped }` with the content of `group` in each match parsed as
+/// `value_type`, one of:
+///
+/// - `"int"`: an optionally signed decimal integer, out of range past `Number.MAX_SAFE_INTEGER`.
+/// - `"float"`: a decimal number such as `-1.5` or `2e10`, out of range if it overflows to
+///   infinity. `inf` and `NaN` are not accepted.
+/// - `"date"`: an ISO 8601 calendar date `YYYY-MM-DD`, valued as milliseconds since the Unix
+///   epoch at midnight UTC, ready for `new Date(value)`. Other date formats are not accepted.
+///
+/// `values` has `{ match_num, content, value, start_utf16, end_utf16 }` for each match that
+/// parsed, `skipped` has `{ match_num, reason }` for the others, with a `reason` of `"invalid"`
+/// or `"out_of_range"`. Matches `group` did not participate in are in neither.
+#[wasm_bindgen]
+pub fn re_extract_typed(
+    text: &str,
+    pattern: &str,
+    group: usize,
+    value_type: &str,
+    flags: &str,
+) -> Result<JsValue, Error> {
+    let value_type = ValueType::parse(value_type)?;
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&extract_typed(&re, text, group, value_type, &flags)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract<'a>(pattern: &str, text: &'a str, value_type: &str) -> ExtractedSer<'a> {
+        let flags = Flags::parse("g");
+        let re = compile(pattern, &flags).unwrap();
+        extract_typed(&re, text, 0, ValueType::parse(value_type).unwrap(), &flags).unwrap()
+    }
+
+    fn skipped(extracted: &ExtractedSer) -> Vec<(usize, SkipReason)> {
+        extracted
+            .skipped
+            .iter()
+            .map(|s| (s.match_num, s.reason))
+            .collect()
+    }
+
+    #[test]
+    fn ints_skip_values_a_js_number_cannot_hold() {
+        let extracted = extract(
+            r"-?\d+",
+            "7 -12 9007199254740991 9007199254740992 99999999999999999999 -9223372036854775808",
+            "int",
+        );
+        let values: Vec<_> = extracted.values.iter().map(|v| v.value).collect();
+        assert_eq!(values, [7.0, -12.0, 9007199254740991.0]);
+        assert_eq!(
+            skipped(&extracted),
+            [
+                (3, SkipReason::OutOfRange),
+                (4, SkipReason::OutOfRange),
+                (5, SkipReason::OutOfRange)
+            ]
+        );
+    }
+
+    #[test]
+    fn floats_need_digits() {
+        let extracted = extract(r"\S+", "1.5 -2e3 inf NaN 1e999", "float");
+        let values: Vec<_> = extracted.values.iter().map(|v| v.value).collect();
+        assert_eq!(values, [1.5, -2000.0]);
+        assert_eq!(
+            skipped(&extracted),
+            [
+                (2, SkipReason::Invalid),
+                (3, SkipReason::Invalid),
+                (4, SkipReason::OutOfRange)
+            ]
+        );
+    }
+
+    #[test]
+    fn dates_are_checked_against_the_calendar() {
+        let extracted = extract(
+            r"\S+",
+            "1970-01-02 2024-02-29 2023-02-29 2000-13-01",
+            "date",
+        );
+        let values: Vec<_> = extracted.values.iter().map(|v| v.value).collect();
+        assert_eq!(values, [86_400_000.0, 1_709_164_800_000.0]);
+        assert_eq!(
+            skipped(&extracted),
+            [(2, SkipReason::Invalid), (3, SkipReason::Invalid)]
+        );
+    }
+
+    #[test]
+    fn unknown_types_and_groups_are_errors() {
+        assert!(matches!(
+            ValueType::parse("bool"),
+            Err(Error::UnknownValueType { .. })
+        ));
+        let flags = Flags::default();
+        let re = compile(r"\d", &flags).unwrap();
+        let err = extract_typed(&re, "1", 1, ValueType::Int, &flags).unwrap_err();
+        assert!(matches!(err, Error::InvalidGroup { group: 1 }));
+    }
+}
@@ -0,0 +1,2223 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt::Write;
+use std::iter::Take;
+use std::mem;
+
+use js_sys::{Array, Map};
+use regex::bytes::{Captures, Regex};
+use serde::Serialize;
+use unicode_segmentation::UnicodeSegmentation;
+use wasm_bindgen::prelude::*;
+
+use crate::error::{Field, FieldError};
+use crate::flags::Flags;
+use crate::options::{OffsetEncoding, Options};
+use crate::utf16::{ceil_char_boundary, utf16_index_bytes, utf16_len, Utf16Cursor, Utf32Cursor};
+use crate::{compile, compile_with, to_js, Error};
+
+/// Version of the [`MatchSer`] layout encoded by [`re_find_bincode`]. Bump it whenever the
+/// fields of `MatchSer`, `CapturesSer` or `CapSer` change.
+pub const BINARY_SCHEMA_VERSION: u32 = 3;
+
+#[derive(Debug, Serialize)]
+pub struct MatchSer<'a> {
+    pub matches: Vec<CapturesSer<'a>>,
+}
+
+/// One match, with an entry for each group that participated in it. Group 0 is the whole match.
+#[derive(Debug, Serialize)]
+pub struct CapturesSer<'a> {
+    pub match_num: usize,
+    /// Whether this match starts where the one before it in the same result ends, so the two
+    /// can be highlighted as one. Always false for the first match.
+    pub adjacent_to_prev: bool,
+    pub groups: Vec<CapSer<'a>>,
+    /// Only set along with the `score` option: the mean of the share of the pattern's groups,
+    /// besides group 0, that participated, or 1 if it has none, and the UTF-16 length of the
+    /// match over that of the longest match of the result, or 1 if all are empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// Whether the match starts inside a quoted string, only set along with the `quote_context`
+    /// option.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_string: Option<bool>,
+    /// Whether the match starts inside parens outside strings, only set along with the
+    /// `quote_context` option.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_parens: Option<bool>,
+    /// The extended grapheme cluster right after the match, or `null` at the end of the input,
+    /// only set along with the `next_char` option.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_char: Option<Option<String>>,
+    /// The rank of the match's UTF-16 length among those of the result, 1 for the longest,
+    /// only set along with the `length_rank` option.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length_rank: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CapSer<'a> {
+    pub group_num: usize,
+    pub name: Option<&'a str>,
+    pub content: Cow<'a, str>,
+    pub start: usize,
+    pub end: usize,
+    /// `None`, and left out of the output, when the caller opted out of UTF-16 offsets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_utf16: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_utf16: Option<usize>,
+    /// The number of extended grapheme clusters in `content`, only set by
+    /// [`re_find_with_grapheme_len`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grapheme_len: Option<usize>,
+    /// The content as matched, only set along with the `collapse_whitespace` option.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_content: Option<Cow<'a, str>>,
+    /// The z-index layer of the group among the groups of its match, only set by
+    /// [`re_find_layered`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<usize>,
+    /// The content without leading and trailing whitespace, only set along with the `trim`
+    /// option. Offsets still span the untrimmed content.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trimmed_content: Option<Cow<'a, str>>,
+    /// The group numbers from group 0 down to this group, dot separated, only set by
+    /// [`crate::tree::re_find_with_paths`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// The content lowercased, as a key to group matches by regardless of case, only set along
+    /// with the `normalize_case` option.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalized_content: Option<String>,
+}
+
+impl MatchSer<'_> {
+    /// Drops the UTF-16 offsets of every group, for callers that only index by bytes.
+    pub(crate) fn omit_utf16(&mut self) {
+        for g in self.matches.iter_mut().flat_map(|m| &mut m.groups) {
+            g.start_utf16 = None;
+            g.end_utf16 = None;
+        }
+    }
+
+    /// Replaces every run of whitespace in the content of each group with one space, keeping
+    /// the original in `raw_content`. Offsets still refer to the original content.
+    pub(crate) fn collapse_whitespace(&mut self) {
+        for g in self.matches.iter_mut().flat_map(|m| &mut m.groups) {
+            let mut collapsed = String::with_capacity(g.content.len());
+            let mut in_whitespace = false;
+            for c in g.content.chars() {
+                if !c.is_whitespace() {
+                    collapsed.push(c);
+                } else if !in_whitespace {
+                    collapsed.push(' ');
+                }
+                in_whitespace = c.is_whitespace();
+            }
+            g.raw_content = Some(mem::replace(&mut g.content, Cow::Owned(collapsed)));
+        }
+    }
+
+    /// Sets the `trimmed_content` of each group.
+    pub(crate) fn trim(&mut self) {
+        for g in self.matches.iter_mut().flat_map(|m| &mut m.groups) {
+            g.trimmed_content = Some(match &g.content {
+                Cow::Borrowed(content) => Cow::Borrowed(content.trim()),
+                Cow::Owned(content) => Cow::Owned(content.trim().to_owned()),
+            });
+        }
+    }
+
+    /// Sets the `normalized_content` of each group.
+    pub(crate) fn normalize_case(&mut self) {
+        for g in self.matches.iter_mut().flat_map(|m| &mut m.groups) {
+            g.normalized_content = Some(g.content.to_lowercase());
+        }
+    }
+
+    /// HTML-escapes the content of each group, and `raw_content` and `trimmed_content` if set.
+    /// Offsets still refer to the unescaped input.
+    pub(crate) fn html_escape(&mut self) {
+        for g in self.matches.iter_mut().flat_map(|m| &mut m.groups) {
+            if let Cow::Owned(escaped) = html_escape(&g.content) {
+                g.content = Cow::Owned(escaped);
+            }
+            for content in [&mut g.raw_content, &mut g.trimmed_content]
+                .into_iter()
+                .flatten()
+            {
+                if let Cow::Owned(escaped) = html_escape(content) {
+                    *content = Cow::Owned(escaped);
+                }
+            }
+        }
+    }
+
+    /// Sorts the groups of each match so a group comes before the groups nested in it. Must run
+    /// before the UTF-16 offsets are dropped.
+    pub(crate) fn sort_nested(&mut self) {
+        for m in &mut self.matches {
+            m.groups
+                .sort_by_key(|g| (g.start_utf16, Reverse(g.end_utf16)));
+        }
+    }
+
+    /// Assigns each group the lowest layer that no overlapping group of its match is on. Spans
+    /// are colored in order of start, longest first, which is the greedy coloring of an
+    /// interval graph and uses as few layers as the deepest overlap needs.
+    pub(crate) fn assign_layers(&mut self) {
+        for m in &mut self.matches {
+            let mut order: Vec<_> = (0..m.groups.len()).collect();
+            order.sort_by_key(|&i| (m.groups[i].start, Reverse(m.groups[i].end)));
+            // The end of the last group put on each layer.
+            let mut layer_ends: Vec<usize> = Vec::new();
+            for i in order {
+                let g = &mut m.groups[i];
+                let layer = match layer_ends.iter().position(|&end| end <= g.start) {
+                    Some(layer) => layer,
+                    None => {
+                        layer_ends.push(0);
+                        layer_ends.len() - 1
+                    }
+                };
+                layer_ends[layer] = g.end;
+                g.layer = Some(layer);
+            }
+        }
+    }
+
+    /// Sets the `score` of each match, for a pattern with `captures_len` groups. Must run before
+    /// the UTF-16 offsets are dropped.
+    pub(crate) fn score(&mut self, captures_len: usize) {
+        let len = |m: &CapturesSer| {
+            let whole = &m.groups[0];
+            whole.end_utf16.unwrap() - whole.start_utf16.unwrap()
+        };
+        let longest = self.matches.iter().map(len).max().unwrap_or(0);
+        for m in &mut self.matches {
+            let participation = match captures_len {
+                1 => 1.0,
+                _ => (m.groups.len() - 1) as f64 / (captures_len - 1) as f64,
+            };
+            let length = match longest {
+                0 => 1.0,
+                _ => len(m) as f64 / longest as f64,
+            };
+            m.score = Some((participation + length) / 2.0);
+        }
+    }
+
+    /// Sets the `length_rank` of each match. Must run before the UTF-16 offsets are dropped.
+    pub(crate) fn rank_lengths(&mut self) {
+        let len = |m: &CapturesSer| {
+            let whole = &m.groups[0];
+            whole.end_utf16.unwrap() - whole.start_utf16.unwrap()
+        };
+        let mut lengths: Vec<_> = self.matches.iter().map(len).collect();
+        lengths.sort_unstable_by_key(|&len| Reverse(len));
+        lengths.dedup();
+        for m in &mut self.matches {
+            let len = len(m);
+            m.length_rank = Some(lengths.partition_point(|&l| l > len) + 1);
+        }
+    }
+
+    /// Sets the `in_string` and `in_parens` of each match from one scan of `text`, the input the
+    /// offsets refer to.
+    pub(crate) fn quote_context(&mut self, text: &str) {
+        let mut scanned = 0;
+        // The quote of the string the scan is in, if any.
+        let mut quote = None;
+        let mut depth = 0usize;
+        for m in &mut self.matches {
+            let start = m.groups[0].start;
+            for &b in &text.as_bytes()[scanned..start] {
+                match (quote, b) {
+                    (Some(q), b) if b == q => quote = None,
+                    (Some(_), _) => {}
+                    (None, b'"' | b'\'') => quote = Some(b),
+                    (None, b'(') => depth += 1,
+                    (None, b')') => depth = depth.saturating_sub(1),
+                    (None, _) => {}
+                }
+            }
+            scanned = start;
+            m.in_string = Some(quote.is_some());
+            m.in_parens = Some(depth > 0);
+        }
+    }
+
+    /// Sets the `next_char` of each match from `text`, the input the offsets refer to. A match
+    /// ending inside a grapheme cluster reports the rest of that cluster.
+    pub(crate) fn next_char(&mut self, text: &str) {
+        for m in &mut self.matches {
+            let end = ceil_char_boundary(text, m.groups[0].end);
+            m.next_char = Some(text[end..].graphemes(true).next().map(str::to_owned));
+        }
+    }
+
+    /// Converts `start` and `end` of each group from bytes of `text` to `encoding`. Must run
+    /// before the UTF-16 offsets are dropped.
+    pub(crate) fn encode_offsets(&mut self, text: &str, encoding: OffsetEncoding) {
+        let mut cursor = Utf32Cursor::new(text);
+        for g in self.matches.iter_mut().flat_map(|m| &mut m.groups) {
+            (g.start, g.end) = match encoding {
+                OffsetEncoding::Utf8 => (g.start, g.end),
+                OffsetEncoding::Utf16 => (g.start_utf16.unwrap(), g.end_utf16.unwrap()),
+                OffsetEncoding::Utf32 => (cursor.utf32(g.start), cursor.utf32(g.end)),
+            };
+        }
+    }
+
+    /// Sets the `path` of each group from `paths`, indexed by group number.
+    pub(crate) fn set_paths(&mut self, paths: &[String]) {
+        for g in self.matches.iter_mut().flat_map(|m| &mut m.groups) {
+            g.path = Some(paths[g.group_num].clone());
+        }
+    }
+
+    pub(crate) fn count_graphemes(&mut self) {
+        for g in self.matches.iter_mut().flat_map(|m| &mut m.groups) {
+            g.grapheme_len = Some(g.content.graphemes(true).count());
+        }
+    }
+}
+
+/// [`MatchSer`] with the content of every group moved into one shared string, so that JS gets
+/// a single string to slice instead of one per group.
+#[derive(Debug, Serialize)]
+pub struct PackedSer<'a> {
+    pub content_blob: String,
+    pub matches: Vec<PackedCapturesSer<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PackedCapturesSer<'a> {
+    pub match_num: usize,
+    pub adjacent_to_prev: bool,
+    pub groups: Vec<PackedCapSer<'a>>,
+}
+
+/// A [`CapSer`] whose content is `content_blob.slice(blob_start, blob_start + blob_len)`, both
+/// in UTF-16 code units.
+#[derive(Debug, Serialize)]
+pub struct PackedCapSer<'a> {
+    pub group_num: usize,
+    pub name: Option<&'a str>,
+    pub blob_start: usize,
+    pub blob_len: usize,
+    pub start: usize,
+    pub end: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_utf16: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_utf16: Option<usize>,
+}
+
+impl<'a> From<MatchSer<'a>> for PackedSer<'a> {
+    fn from(matches: MatchSer<'a>) -> Self {
+        let mut content_blob = String::new();
+        let mut blob_len_utf16 = 0;
+        let matches = matches
+            .matches
+            .into_iter()
+            .map(|m| {
+                let whole = &m.groups[0];
+                let whole_blob_start = blob_len_utf16;
+                let whole_is_text = matches!(whole.content, Cow::Borrowed(_));
+                let whole_start_utf16 = whole.start_utf16;
+                let groups = m
+                    .groups
+                    .into_iter()
+                    .map(|g| {
+                        let blob_len = g.content.encode_utf16().count();
+                        // Groups inside a match that is plain text are already in the blob,
+                        // unless they split a char and their content is escaped.
+                        let group_is_text = matches!(g.content, Cow::Borrowed(_));
+                        let blob_start = match (g.start_utf16, whole_start_utf16) {
+                            (Some(start), Some(whole_start))
+                                if g.group_num > 0 && whole_is_text && group_is_text =>
+                            {
+                                whole_blob_start + start - whole_start
+                            }
+                            _ => {
+                                content_blob.push_str(&g.content);
+                                blob_len_utf16 += blob_len;
+                                blob_len_utf16 - blob_len
+                            }
+                        };
+                        PackedCapSer {
+                            group_num: g.group_num,
+                            name: g.name,
+                            blob_start,
+                            blob_len,
+                            start: g.start,
+                            end: g.end,
+                            start_utf16: g.start_utf16,
+                            end_utf16: g.end_utf16,
+                        }
+                    })
+                    .collect();
+                PackedCapturesSer {
+                    match_num: m.match_num,
+                    adjacent_to_prev: m.adjacent_to_prev,
+                    groups,
+                }
+            })
+            .collect();
+        PackedSer {
+            content_blob,
+            matches,
+        }
+    }
+}
+
+/// Matched bytes as a string, with bytes that are not valid UTF-8 (only possible without the
+/// `u` flag) escaped as `\x..`.
+pub(crate) fn content(bytes: &[u8]) -> Cow<'_, str> {
+    if let Ok(s) = std::str::from_utf8(bytes) {
+        return Cow::Borrowed(s);
+    }
+    let mut escaped = String::new();
+    for chunk in bytes.utf8_chunks() {
+        escaped.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            write!(escaped, "\\x{:02X}", byte).unwrap();
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// `s` with `<`, `>`, `&`, `"` and `'` replaced by HTML character references.
+fn html_escape(s: &str) -> Cow<'_, str> {
+    if !s.contains(['<', '>', '&', '"', '\'']) {
+        return Cow::Borrowed(s);
+    }
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Iterates over successive non-overlapping captures like `Regex::captures_iter`, but starting
+/// at byte `start` of the haystack so that anchors still see the text before it. Empty matches
+/// inside a char are skipped in unicode mode, as `regex::Regex` would.
+pub(crate) struct CapturesIter<'r, 'h> {
+    re: &'r Regex,
+    haystack: &'h [u8],
+    pos: usize,
+    last_end: Option<usize>,
+    unicode: bool,
+}
+
+impl<'h> Iterator for CapturesIter<'_, 'h> {
+    type Item = Captures<'h>;
+
+    fn next(&mut self) -> Option<Captures<'h>> {
+        loop {
+            if self.pos > self.haystack.len() {
+                return None;
+            }
+            let caps = self.re.captures_at(self.haystack, self.pos)?;
+            let m = caps.get(0).unwrap();
+            if m.is_empty() {
+                self.pos = m.end() + 1;
+                if Some(m.end()) == self.last_end
+                    || (self.unicode && is_continuation_byte(self.haystack.get(m.start())))
+                {
+                    continue;
+                }
+            } else {
+                self.pos = m.end();
+            }
+            self.last_end = Some(m.end());
+            return Some(caps);
+        }
+    }
+}
+
+impl<'r, 'h> CapturesIter<'r, 'h> {
+    /// Carries on iterating from a [`CapturesIter::position`] of an earlier iterator over the
+    /// same haystack.
+    pub(crate) fn resume(
+        re: &'r Regex,
+        haystack: &'h [u8],
+        (pos, last_end): (usize, Option<usize>),
+        flags: &Flags,
+    ) -> Self {
+        CapturesIter {
+            re,
+            haystack,
+            pos,
+            last_end,
+            unicode: flags.unicode,
+        }
+    }
+
+    /// Where the search for the next captures starts, and where the last captures ended.
+    pub(crate) fn position(&self) -> (usize, Option<usize>) {
+        (self.pos, self.last_end)
+    }
+}
+
+/// Captures of `re` in `haystack` from `start` on, limited to the first one unless the `g` flag
+/// is set.
+pub(crate) fn captures_iter<'r, 'h>(
+    re: &'r Regex,
+    haystack: &'h str,
+    start: usize,
+    flags: &Flags,
+) -> Take<CapturesIter<'r, 'h>> {
+    captures_iter_bytes(re, haystack.as_bytes(), start, flags)
+}
+
+/// [`captures_iter`] over a haystack that may not be valid UTF-8.
+fn captures_iter_bytes<'r, 'h>(
+    re: &'r Regex,
+    haystack: &'h [u8],
+    start: usize,
+    flags: &Flags,
+) -> Take<CapturesIter<'r, 'h>> {
+    CapturesIter {
+        re,
+        haystack,
+        pos: start,
+        last_end: None,
+        unicode: flags.unicode,
+    }
+    .take(if flags.global { usize::MAX } else { 1 })
+}
+
+fn is_continuation_byte(byte: Option<&u8>) -> bool {
+    byte.is_some_and(|&b| (b as i8) < -0x40)
+}
+
+/// Serializes `captures`, found in a haystack starting at byte `offset` of `text`, with offsets
+/// relative to `text`. Each captures is paired with its match number, so that filtering what is
+/// reported keeps the numbers of the initial scan.
+fn collect<'a>(
+    re: &'a Regex,
+    text: &'a [u8],
+    offset: usize,
+    captures: impl Iterator<Item = (usize, Captures<'a>)>,
+) -> MatchSer<'a> {
+    let mut cursor = Utf16Cursor::from_bytes(text);
+    collect_resumed(re, offset, captures, &mut cursor, &mut None)
+}
+
+/// [`collect`] continuing from the state of an earlier call: `cursor` over the same text and
+/// `prev_end`, the end of the last match reported, both updated for the next call.
+pub(crate) fn collect_resumed<'a>(
+    re: &'a Regex,
+    offset: usize,
+    captures: impl Iterator<Item = (usize, Captures<'a>)>,
+    cursor: &mut Utf16Cursor,
+    prev_end: &mut Option<usize>,
+) -> MatchSer<'a> {
+    let names: Vec<_> = re.capture_names().collect();
+    let matches = captures
+        .map(|(match_num, caps)| {
+            let whole = caps.get(0).unwrap();
+            let adjacent_to_prev = *prev_end == Some(whole.start() + offset);
+            *prev_end = Some(whole.end() + offset);
+            let groups = caps
+                .iter()
+                .enumerate()
+                .filter_map(|(group_num, m)| {
+                    let m = m?;
+                    let (start, end) = (m.start() + offset, m.end() + offset);
+                    Some(CapSer {
+                        group_num,
+                        name: names[group_num],
+                        content: content(m.as_bytes()),
+                        start,
+                        end,
+                        start_utf16: Some(cursor.utf16(start)),
+                        end_utf16: Some(cursor.utf16(end)),
+                        grapheme_len: None,
+                        raw_content: None,
+                        layer: None,
+                        trimmed_content: None,
+                        path: None,
+                        normalized_content: None,
+                    })
+                })
+                .collect();
+            CapturesSer {
+                match_num,
+                adjacent_to_prev,
+                groups,
+                score: None,
+                in_string: None,
+                in_parens: None,
+                next_char: None,
+                length_rank: None,
+            }
+        })
+        .collect();
+    MatchSer { matches }
+}
+
+pub(crate) fn find<'a>(re: &'a Regex, text: &'a str, flags: &Flags) -> MatchSer<'a> {
+    collect(
+        re,
+        text.as_bytes(),
+        0,
+        captures_iter(re, text, 0, flags).enumerate(),
+    )
+}
+
+/// [`find`] reporting every match whether or not `flags` has `g`.
+pub(crate) fn find_all<'a>(re: &'a Regex, text: &'a str, flags: &Flags) -> MatchSer<'a> {
+    let flags = Flags {
+        global: true,
+        ..*flags
+    };
+    find(re, text, &flags)
+}
+
+/// [`find`] reporting only the first match whether or not `flags` has `g`.
+pub(crate) fn find_one<'a>(re: &'a Regex, text: &'a str, flags: &Flags) -> MatchSer<'a> {
+    let flags = Flags {
+        global: false,
+        ..*flags
+    };
+    find(re, text, &flags)
+}
+
+/// [`find`] over bytes that may not be valid UTF-8. UTF-16 offsets are those of the string
+/// JS gets from decoding `bytes` with a `TextDecoder`.
+pub(crate) fn find_bytes<'a>(re: &'a Regex, bytes: &'a [u8], flags: &Flags) -> MatchSer<'a> {
+    collect(
+        re,
+        bytes,
+        0,
+        captures_iter_bytes(re, bytes, 0, flags).enumerate(),
+    )
+}
+
+/// Only the matches where `group` participated with a UTF-16 length in `min_len..=max_len`, no
+/// upper bound when `max_len` is `None`.
+pub(crate) fn find_filtered<'a>(
+    re: &'a Regex,
+    text: &'a str,
+    min_len: usize,
+    max_len: Option<usize>,
+    group: usize,
+    flags: &Flags,
+) -> Result<MatchSer<'a>, Error> {
+    if group >= re.captures_len() {
+        return Err(Error::InvalidGroup { group });
+    }
+    let captures = captures_iter(re, text, 0, flags)
+        .enumerate()
+        .filter(|(_, caps)| {
+            caps.get(group).is_some_and(|m| {
+                let len = utf16_len(text, m.start(), m.end());
+                len >= min_len && max_len.is_none_or(|max_len| len <= max_len)
+            })
+        });
+    Ok(collect(re, text.as_bytes(), 0, captures))
+}
+
+/// Only the matches whose content `filter` also matches somewhere.
+pub(crate) fn find_filtered_by<'a>(
+    re: &'a Regex,
+    filter: &Regex,
+    text: &'a str,
+    flags: &Flags,
+) -> MatchSer<'a> {
+    let captures = captures_iter(re, text, 0, flags)
+        .enumerate()
+        .filter(|(_, caps)| filter.is_match(caps.get(0).unwrap().as_bytes()));
+    collect(re, text.as_bytes(), 0, captures)
+}
+
+/// One group across every match as parallel arrays, indexed by match. Matches the group did not
+/// participate in have `None` content and -1 offsets.
+#[derive(Debug, Serialize)]
+pub struct GroupColumnSer<'a> {
+    pub contents: Vec<Option<Cow<'a, str>>>,
+    pub starts_utf16: Vec<i32>,
+    pub ends_utf16: Vec<i32>,
+}
+
+pub(crate) fn group_column<'a>(
+    re: &Regex,
+    text: &'a str,
+    group: usize,
+    flags: &Flags,
+) -> Result<GroupColumnSer<'a>, Error> {
+    if group >= re.captures_len() {
+        return Err(Error::InvalidGroup { group });
+    }
+    let mut cursor = Utf16Cursor::new(text);
+    let mut column = GroupColumnSer {
+        contents: Vec::new(),
+        starts_utf16: Vec::new(),
+        ends_utf16: Vec::new(),
+    };
+    for caps in captures_iter(re, text, 0, flags) {
+        let (content, start, end) = match caps.get(group) {
+            Some(m) => (
+                Some(content(m.as_bytes())),
+                cursor.utf16(m.start()) as i32,
+                cursor.utf16(m.end()) as i32,
+            ),
+            None => (None, -1, -1),
+        };
+        column.contents.push(content);
+        column.starts_utf16.push(start);
+        column.ends_utf16.push(end);
+    }
+    Ok(column)
+}
+
+/// Version of the [`RangesCollectionSer`] schema. Bump it whenever its fields, or those of
+/// `RangeFeatureSer` and `RangePropertiesSer`, change.
+pub const RANGES_SCHEMA_VERSION: u32 = 1;
+
+/// Matches in the generic shape of range visualization libraries, see
+/// [`re_find_ranges_collection`].
+#[derive(Debug, Serialize)]
+pub struct RangesCollectionSer {
+    /// Always `"matches"`.
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub schema_version: u32,
+    pub features: Vec<RangeFeatureSer>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RangeFeatureSer {
+    /// The index of the feature in `features`.
+    pub id: usize,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+    pub properties: RangePropertiesSer,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RangePropertiesSer {
+    pub group_num: usize,
+    pub match_num: usize,
+}
+
+impl From<Vec<CaptureOffsetSer>> for RangesCollectionSer {
+    fn from(offsets: Vec<CaptureOffsetSer>) -> Self {
+        let features = offsets
+            .into_iter()
+            .enumerate()
+            .map(|(id, offset)| RangeFeatureSer {
+                id,
+                start_utf16: offset.start_utf16,
+                end_utf16: offset.end_utf16,
+                properties: RangePropertiesSer {
+                    group_num: offset.group_num,
+                    match_num: offset.match_num,
+                },
+            })
+            .collect();
+        RangesCollectionSer {
+            kind: "matches",
+            schema_version: RANGES_SCHEMA_VERSION,
+            features,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatsSer {
+    pub total_matches: usize,
+    pub total_bytes_matched: usize,
+    /// The sum of `end_utf16 - start_utf16` over the matches.
+    pub total_utf16_matched: usize,
+}
+
+pub(crate) fn stats(re: &Regex, text: &str, flags: &Flags) -> StatsSer {
+    let mut cursor = Utf16Cursor::new(text);
+    let mut stats = StatsSer {
+        total_matches: 0,
+        total_bytes_matched: 0,
+        total_utf16_matched: 0,
+    };
+    for caps in captures_iter(re, text, 0, flags) {
+        let m = caps.get(0).unwrap();
+        let start_utf16 = cursor.utf16(m.start());
+        stats.total_matches += 1;
+        stats.total_bytes_matched += m.len();
+        stats.total_utf16_matched += cursor.utf16(m.end()) - start_utf16;
+    }
+    stats
+}
+
+/// A run of matches with short gaps between them, see [`re_find_clustered`].
+#[derive(Debug, Serialize)]
+pub struct ClusterSer {
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+    pub match_count: usize,
+}
+
+pub(crate) fn clusters(
+    re: &Regex,
+    text: &str,
+    gap_threshold_utf16: usize,
+    flags: &Flags,
+) -> Vec<ClusterSer> {
+    let mut cursor = Utf16Cursor::new(text);
+    let mut clusters: Vec<ClusterSer> = Vec::new();
+    for caps in captures_iter(re, text, 0, flags) {
+        let m = caps.get(0).unwrap();
+        let start_utf16 = cursor.utf16(m.start());
+        let end_utf16 = cursor.utf16(m.end());
+        match clusters.last_mut() {
+            Some(cluster) if start_utf16 - cluster.end_utf16 < gap_threshold_utf16 => {
+                cluster.end_utf16 = end_utf16;
+                cluster.match_count += 1;
+            }
+            _ => clusters.push(ClusterSer {
+                start_utf16,
+                end_utf16,
+                match_count: 1,
+            }),
+        }
+    }
+    clusters
+}
+
+/// The serialized size assumed for a match besides the content of its groups, in bytes: field
+/// names, offsets and punctuation.
+const MATCH_OVERHEAD: usize = 128;
+
+/// The matches of a scan that fit a size budget, see [`re_find_size_capped`].
+#[derive(Debug, Serialize)]
+pub struct SizeCappedSer<'a> {
+    #[serde(flatten)]
+    pub matches: MatchSer<'a>,
+    /// Whether matches were left out to stay within the budget.
+    pub truncated: bool,
+    /// The start of the first match left out, only set when `truncated`.
+    pub resume_from: Option<usize>,
+    pub resume_from_utf16: Option<usize>,
+}
+
+/// The matches up to the last one whose estimated size keeps the running total within
+/// `max_bytes`.
+pub(crate) fn size_capped<'a>(
+    re: &'a Regex,
+    text: &'a str,
+    max_bytes: usize,
+    flags: &Flags,
+) -> SizeCappedSer<'a> {
+    let mut kept = Vec::new();
+    let mut size = 0;
+    let mut resume_from = None;
+    for (match_num, caps) in captures_iter(re, text, 0, flags).enumerate() {
+        let estimate = caps
+            .iter()
+            .flatten()
+            .map(|m| utf16_len(text, m.start(), m.end()))
+            .sum::<usize>()
+            + MATCH_OVERHEAD;
+        if size + estimate > max_bytes {
+            resume_from = Some(caps.get(0).unwrap().start());
+            break;
+        }
+        size += estimate;
+        kept.push((match_num, caps));
+    }
+    SizeCappedSer {
+        matches: collect(re, text.as_bytes(), 0, kept.into_iter()),
+        truncated: resume_from.is_some(),
+        resume_from,
+        resume_from_utf16: resume_from.map(|start| utf16_index_bytes(text, start)),
+    }
+}
+
+/// The first and last matches of a scan, see [`re_find_head_tail`].
+#[derive(Debug, Serialize)]
+pub struct HeadTailSer<'a> {
+    pub head: Vec<CapturesSer<'a>>,
+    pub tail: Vec<CapturesSer<'a>>,
+    pub total: usize,
+    /// How many matches between `head` and `tail` were left out.
+    pub omitted: usize,
+}
+
+/// The first `head` and last `tail` matches, scanning every match once without keeping the ones
+/// in between. A match is never in both lists.
+pub(crate) fn head_tail<'a>(
+    re: &'a Regex,
+    text: &'a str,
+    head: usize,
+    tail: usize,
+    flags: &Flags,
+) -> HeadTailSer<'a> {
+    let flags = Flags {
+        global: true,
+        ..*flags
+    };
+    let mut first = Vec::new();
+    let mut last = VecDeque::new();
+    let mut total = 0;
+    for (match_num, caps) in captures_iter(re, text, 0, &flags).enumerate() {
+        total += 1;
+        if first.len() < head {
+            first.push((match_num, caps));
+        } else if tail > 0 {
+            if last.len() == tail {
+                last.pop_front();
+            }
+            last.push_back((match_num, caps));
+        }
+    }
+    let omitted = total - first.len() - last.len();
+    HeadTailSer {
+        head: collect(re, text.as_bytes(), 0, first.into_iter()).matches,
+        tail: collect(re, text.as_bytes(), 0, last.into_iter()).matches,
+        total,
+        omitted,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct OccurrenceSer<'a> {
+    pub match_num: usize,
+    pub content: Cow<'a, str>,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+}
+
+/// The coordinates of one group of one match, see [`re_capture_offsets`].
+#[derive(Debug, Serialize)]
+pub struct CaptureOffsetSer {
+    pub match_num: usize,
+    pub group_num: usize,
+    pub start: usize,
+    pub end: usize,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+}
+
+/// The offsets [`find`] reports, flattened across matches and without content.
+pub(crate) fn capture_offsets(re: &Regex, text: &str, flags: &Flags) -> Vec<CaptureOffsetSer> {
+    let mut cursor = Utf16Cursor::new(text);
+    let mut offsets = Vec::new();
+    for (match_num, caps) in captures_iter(re, text, 0, flags).enumerate() {
+        for (group_num, m) in caps.iter().enumerate() {
+            let Some(m) = m else { continue };
+            offsets.push(CaptureOffsetSer {
+                match_num,
+                group_num,
+                start: m.start(),
+                end: m.end(),
+                start_utf16: cursor.utf16(m.start()),
+                end_utf16: cursor.utf16(m.end()),
+            });
+        }
+    }
+    offsets
+}
+
+/// Every match the group called `name` participated in.
+pub(crate) fn named_group_occurrences<'a>(
+    re: &Regex,
+    text: &'a str,
+    name: &str,
+    flags: &Flags,
+) -> Result<Vec<OccurrenceSer<'a>>, Error> {
+    let Some(group) = re.capture_names().position(|n| n == Some(name)) else {
+        return Err(Error::UnknownGroupName {
+            name: name.to_string(),
+        });
+    };
+    let mut cursor = Utf16Cursor::new(text);
+    Ok(captures_iter(re, text, 0, flags)
+        .enumerate()
+        .filter_map(|(match_num, caps)| {
+            let m = caps.get(group)?;
+            Some(OccurrenceSer {
+                match_num,
+                content: content(m.as_bytes()),
+                start_utf16: cursor.utf16(m.start()),
+                end_utf16: cursor.utf16(m.end()),
+            })
+        })
+        .collect())
+}
+
+/// Keys with their values, in order of first appearance.
+type GroupIndex<'a> = Vec<(Cow<'a, str>, Vec<Cow<'a, str>>)>;
+
+/// The content of `value_group` for each content of `key_group`, with one value per match where
+/// both groups participated.
+pub(crate) fn index_by_group<'a>(
+    re: &Regex,
+    text: &'a str,
+    key_group: usize,
+    value_group: usize,
+    flags: &Flags,
+) -> Result<GroupIndex<'a>, Error> {
+    for group in [key_group, value_group] {
+        if group >= re.captures_len() {
+            return Err(Error::InvalidGroup { group });
+        }
+    }
+    let mut index: GroupIndex<'a> = Vec::new();
+    let mut positions: HashMap<Cow<str>, usize> = HashMap::new();
+    for caps in captures_iter(re, text, 0, flags) {
+        let (Some(key), Some(value)) = (caps.get(key_group), caps.get(value_group)) else {
+            continue;
+        };
+        let key = content(key.as_bytes());
+        let value = content(value.as_bytes());
+        match positions.get(&key) {
+            Some(&i) => index[i].1.push(value),
+            None => {
+                positions.insert(key.clone(), index.len());
+                index.push((key, vec![value]));
+            }
+        }
+    }
+    Ok(index)
+}
+
+/// The content of every named group for each match, in the order the names appear in the
+/// pattern. `None` marks matches the group did not participate in.
+pub(crate) fn named_values<'a>(
+    re: &'a Regex,
+    text: &'a str,
+    flags: &Flags,
+) -> Vec<(&'a str, Vec<Option<Cow<'a, str>>>)> {
+    let mut named: Vec<_> = re
+        .capture_names()
+        .enumerate()
+        .filter_map(|(group_num, name)| Some((group_num, name?, Vec::new())))
+        .collect();
+    for caps in captures_iter(re, text, 0, flags) {
+        for (group_num, _, values) in &mut named {
+            values.push(caps.get(*group_num).map(|m| content(m.as_bytes())));
+        }
+    }
+    named
+        .into_iter()
+        .map(|(_, name, values)| (name, values))
+        .collect()
+}
+
+/// The longest match content, by UTF-16 length, that is found in every one of `inputs`. Every
+/// match of each input is considered, whether or not the `g` flag is set. Ties go to the
+/// lexicographically smallest content.
+pub(crate) fn common_match(re: &Regex, inputs: &[String], flags: &Flags) -> Option<String> {
+    let flags = Flags {
+        global: true,
+        ..*flags
+    };
+    let distinct = |text: &str| -> BTreeSet<String> {
+        captures_iter(re, text, 0, &flags)
+            .map(|caps| content(caps.get(0).unwrap().as_bytes()).into_owned())
+            .collect()
+    };
+    let (first, rest) = inputs.split_first()?;
+    let mut common = distinct(first);
+    for text in rest {
+        let matches = distinct(text);
+        common.retain(|m| matches.contains(m));
+    }
+    common
+        .into_iter()
+        .rev()
+        .max_by_key(|m| m.encode_utf16().count())
+}
+
+pub(crate) fn find_in_range<'a>(
+    re: &'a Regex,
+    text: &'a str,
+    start: usize,
+    end: usize,
+    anchor_to_range: bool,
+    flags: &Flags,
+) -> Result<MatchSer<'a>, Error> {
+    if start > end || !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+        return Err(Error::InvalidRange { start, end });
+    }
+    Ok(if anchor_to_range {
+        collect(
+            re,
+            text.as_bytes(),
+            start,
+            captures_iter(re, &text[start..end], 0, flags).enumerate(),
+        )
+    } else {
+        let captures = captures_iter(re, &text[..end], start, flags);
+        collect(re, text.as_bytes(), 0, captures.enumerate())
+    })
+}
+
+/// Finds the matches of `pattern` in `text`, only the first one unless `flags` contains `g`.
+#[wasm_bindgen]
+pub fn re_find(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&find(&re, text, &flags)))
+}
+
+/// Like [`re_find`], but the groups of each match are sorted by `start_utf16` and then by
+/// descending `end_utf16` instead of by `group_num`, so that a group comes before the groups
+/// nested in it. Groups with the same span stay in `group_num` order. Useful to draw nested
+/// highlights in one pass.
+#[wasm_bindgen]
+pub fn re_find_nested(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let mut matches = find(&re, text, &flags);
+    matches.sort_nested();
+    Ok(to_js(&matches))
+}
+
+/// Like [`re_find`], but also reports the `grapheme_len` of each group: the number of
+/// user-perceived characters in its content, where an emoji sequence like 🏴‍☠️ counts as one.
+/// Escaped bytes that are not valid UTF-8 count as written.
+#[wasm_bindgen]
+pub fn re_find_with_grapheme_len(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let mut matches = find(&re, text, &flags);
+    matches.count_graphemes();
+    Ok(to_js(&matches))
+}
+
+/// Like [`re_find`], but gives each group a `layer` to draw it on, so that overlapping groups
+/// of a match, such as a group and the groups nested in it, are on different layers while
+/// groups that don't overlap can share one. Layers start at 0 and a group's layer is higher
+/// than that of any group it is nested in. Empty groups are on a higher layer than a group
+/// that strictly contains them.
+#[wasm_bindgen]
+pub fn re_find_layered(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let mut matches = find(&re, text, &flags);
+    matches.assign_layers();
+    Ok(to_js(&matches))
+}
+
+/// Like [`re_find`], but takes the input as a `Uint8Array` that does not have to be valid
+/// UTF-8. Byte offsets index `bytes`, UTF-16 offsets index the string from decoding it with a
+/// `TextDecoder`, where each invalid sequence is one U+FFFD. Matched bytes that are not valid
+/// UTF-8 are reported with `\x..` escapes, as without the `u` flag.
+#[wasm_bindgen]
+pub fn re_find_u8(bytes: &[u8], pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&find_bytes(&re, bytes, &flags)))
+}
+
+/// Like [`re_find`], but takes the input as UTF-16 code units, such as a `Uint16Array`, so that
+/// lone surrogates survive the call. They are matched as U+FFFD, which is also one code unit, so
+/// `start_utf16` and `end_utf16` index `units` directly. `start` and `end` are still byte
+/// offsets, into the UTF-8 encoding of the input with lone surrogates replaced.
+#[wasm_bindgen]
+pub fn re_find_utf16(units: &[u16], pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let text = String::from_utf16_lossy(units);
+    Ok(to_js(&find(&re, &text, &flags)))
+}
+
+/// [`find`] with the post-processing that `options` asks for, see [`re_find_with_options`].
+pub(crate) fn find_with_options<'a>(
+    re: &'a Regex,
+    text: &'a str,
+    flags: &Flags,
+    options: &Options,
+) -> MatchSer<'a> {
+    let mut matches = find(re, text, flags);
+    if options.score {
+        matches.score(re.captures_len());
+    }
+    if options.length_rank {
+        matches.rank_lengths();
+    }
+    if options.quote_context {
+        matches.quote_context(text);
+    }
+    if options.next_char {
+        matches.next_char(text);
+    }
+    matches.encode_offsets(text, options.offset_encoding);
+    if options.omit_utf16 {
+        matches.omit_utf16();
+    }
+    if options.normalize_case {
+        matches.normalize_case();
+    }
+    if options.collapse_whitespace {
+        matches.collapse_whitespace();
+    }
+    if options.trim {
+        matches.trim();
+    }
+    if options.html_escape {
+        matches.html_escape();
+    }
+    if options.reverse {
+        matches.matches.reverse();
+    }
+    matches
+}
+
+/// Like [`re_find`], configured by an [`Options`] object.
+#[wasm_bindgen]
+pub fn re_find_with_options(
+    text: &str,
+    pattern: &str,
+    flags: &str,
+    options: JsValue,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let options = Options::from_js(options)?;
+    let re = compile_with(pattern, &flags, &options)?;
+    Ok(to_js(&find_with_options(&re, text, &flags, &options)))
+}
+
+/// Like [`re_find`], but only reports the matches where `group` has a UTF-16 length between
+/// `min_len` and `max_len` inclusive, or no upper bound if `max_len` is `null`. Matches `group`
+/// did not participate in are skipped. `match_num` still counts the skipped matches.
+#[wasm_bindgen]
+pub fn re_find_filtered(
+    text: &str,
+    pattern: &str,
+    min_len: usize,
+    max_len: Option<usize>,
+    group: usize,
+    flags: &str,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let matches = find_filtered(&re, text, min_len, max_len, group, &flags)?;
+    Ok(to_js(&matches))
+}
+
+/// Like [`re_find`], but only reports the matches whose content `filter_pattern` also matches,
+/// unanchored, e.g. the URLs containing `login`. Both patterns are compiled with `flags`, and
+/// a compile error has a `field` of `"pattern"` or `"filter_pattern"`. `match_num` still counts
+/// the skipped matches.
+#[wasm_bindgen]
+pub fn re_find_filtered_by(
+    text: &str,
+    pattern: &str,
+    filter_pattern: &str,
+    flags: &str,
+) -> Result<JsValue, FieldError> {
+    let flags = Flags::parse(flags);
+    let compile_field =
+        |pattern, field| compile(pattern, &flags).map_err(|error| FieldError { field, error });
+    let re = compile_field(pattern, Field::Pattern)?;
+    let filter = compile_field(filter_pattern, Field::FilterPattern)?;
+    Ok(to_js(&find_filtered_by(&re, &filter, text, &flags)))
+}
+
+/// Returns the longest content that `pattern` matches in every string of `inputs`, or `null` if
+/// there is none. All matches of each input are considered, with or without the `g` flag.
+#[wasm_bindgen]
+pub fn re_common_match(
+    inputs: Vec<String>,
+    pattern: &str,
+    flags: &str,
+) -> Result<Option<String>, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(common_match(&re, &inputs, &flags))
+}
+
+/// Like [`re_find`], but returns a [`PackedSer`] where the content of all groups is transferred
+/// as one string, for large result sets.
+#[wasm_bindgen]
+pub fn re_find_packed(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&PackedSer::from(find(&re, text, &flags))))
+}
+
+/// Like [`re_find`], but returns one flat array of
+/// `{ match_num, group_num, start, end, start_utf16, end_utf16 }` for every group of every
+/// match, without content, for callers that already have the text. Groups that did not
+/// participate in a match are left out.
+#[wasm_bindgen]
+pub fn re_capture_offsets(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&capture_offsets(&re, text, &flags)))
+}
+
+/// Returns `{ total_matches, total_bytes_matched, total_utf16_matched }` for the matches
+/// [`re_find`] would report, without serializing them. The UTF-16 total adds up the spans of the
+/// matches as `re_find` reports them.
+#[wasm_bindgen]
+pub fn re_find_stats(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&stats(&re, text, &flags)))
+}
+
+/// Returns `{ head, tail, total, omitted }` with the first `head` and the last `tail` matches of
+/// `pattern` in `text`, for lists that collapse the middle of a large result set. Every match is
+/// counted whether or not `flags` contains `g`, and a match in `head` is never repeated in
+/// `tail`. The matches in between are counted in `omitted` but never serialized.
+#[wasm_bindgen]
+pub fn re_find_head_tail(
+    text: &str,
+    pattern: &str,
+    head: usize,
+    tail: usize,
+    flags: &str,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&head_tail(&re, text, head, tail, &flags)))
+}
+
+/// Like [`re_find`], but stops before the matches would exceed `max_bytes` once serialized, to
+/// keep each message to JS within a budget, and returns `{ matches, truncated, resume_from,
+/// resume_from_utf16 }`. The size of a match is estimated as the UTF-16 length of the content
+/// of its groups plus a fixed 128 bytes. When `truncated` is set, the rest of the matches can be
+/// found with `re_find_in_range` from the byte offset `resume_from`, the start of the first
+/// match left out. A first match larger than the budget is left out too, so `matches` can be
+/// empty while `truncated` is set.
+#[wasm_bindgen]
+pub fn re_find_size_capped(
+    text: &str,
+    pattern: &str,
+    max_bytes: usize,
+    flags: &str,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&size_capped(&re, text, max_bytes, &flags)))
+}
+
+/// Groups the matches of `pattern` into clusters for an overview gutter, returning
+/// `{ start_utf16, end_utf16, match_count }` for each. A match joins the cluster before it when
+/// the gap between them, from the end of the cluster to the start of the match in UTF-16 code
+/// units, is less than `gap_threshold_utf16`. A gap of exactly the threshold starts a new
+/// cluster, so with a threshold of 0 every match is its own cluster and with 1 only touching
+/// matches merge. Only the first match is clustered unless `flags` contains `g`.
+#[wasm_bindgen]
+pub fn re_find_clustered(
+    text: &str,
+    pattern: &str,
+    gap_threshold_utf16: usize,
+    flags: &str,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&clusters(&re, text, gap_threshold_utf16, &flags)))
+}
+
+/// Like [`re_capture_offsets`], but in the feature collection shape expected by generic range
+/// visualizers: `{ type: "matches", schema_version, features }` where each feature is
+/// `{ id, start_utf16, end_utf16, properties: { group_num, match_num } }`. `schema_version` is
+/// currently 1 and changes whenever this shape does.
+#[wasm_bindgen]
+pub fn re_find_ranges_collection(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let collection = RangesCollectionSer::from(capture_offsets(&re, text, &flags));
+    Ok(to_js(&collection))
+}
+
+/// Returns `{ contents, starts_utf16, ends_utf16 }`, the content and UTF-16 span of `group` in
+/// every match as parallel arrays, for feeding a single column to a chart or table without
+/// walking full `re_find` results. Matches the group did not participate in get `null` content
+/// and -1 offsets, so index `i` of each array is match `i`.
+#[wasm_bindgen]
+pub fn re_group_column(
+    text: &str,
+    pattern: &str,
+    group: usize,
+    flags: &str,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&group_column(&re, text, group, &flags)?))
+}
+
+/// Returns only the content and UTF-16 span of the group called `name`, for each match it
+/// participated in, as `{ match_num, content, start_utf16, end_utf16 }`.
+#[wasm_bindgen]
+pub fn re_named_group_occurrences(
+    text: &str,
+    pattern: &str,
+    name: &str,
+    flags: &str,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&named_group_occurrences(&re, text, name, &flags)?))
+}
+
+/// Like [`re_find`], but encodes the result with postcard for hosts that deserialize it with
+/// serde instead of reading JS values. The payload is the tuple
+/// `(BINARY_SCHEMA_VERSION: u32, MatchSer)`, currently version 3, so decoders can reject
+/// layouts they don't know. The UTF-16 offsets are always present, encoded as `Some`, and
+/// `grapheme_len`, `raw_content`, `layer`, `trimmed_content`, `path`, `normalized_content`,
+/// `score`, `in_string`, `in_parens`, `next_char` and `length_rank` are never set so they are
+/// left out.
+#[wasm_bindgen]
+pub fn re_find_bincode(text: &str, pattern: &str, flags: &str) -> Result<Vec<u8>, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let matches = find(&re, text, &flags);
+    postcard::to_allocvec(&(BINARY_SCHEMA_VERSION, matches)).map_err(|e| Error::Other {
+        message: e.to_string(),
+    })
+}
+
+/// Like [`re_find`], but always reports every match whether or not `flags` contains `g`.
+#[wasm_bindgen]
+pub fn re_find_all(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&find_all(&re, text, &flags)))
+}
+
+/// Like [`re_find`], but only ever reports the first match, even if `flags` contains `g`.
+#[wasm_bindgen]
+pub fn re_find_one(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&find_one(&re, text, &flags)))
+}
+
+/// Like [`re_find`], but only searches `text[start_byte..end_byte]`. Offsets are still relative
+/// to the whole of `text`. The end of the range is treated as the end of the input, while `^`
+/// and `\A` only match at the real start of `text` unless `anchor_to_range` is set, in which
+/// case the range is searched as if it were the whole input. Both bounds must be on char
+/// boundaries.
+#[wasm_bindgen]
+pub fn re_find_in_range(
+    text: &str,
+    pattern: &str,
+    start_byte: usize,
+    end_byte: usize,
+    flags: &str,
+    anchor_to_range: bool,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let matches = find_in_range(&re, text, start_byte, end_byte, anchor_to_range, &flags)?;
+    Ok(to_js(&matches))
+}
+
+/// Returns a JS `Map` from each named group, in pattern order, to an array with its content in
+/// every match, or `null` for matches it did not participate in. Unnamed groups are skipped.
+#[wasm_bindgen]
+pub fn re_find_named_map(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let map = Map::new();
+    for (name, values) in named_values(&re, text, &flags) {
+        let values: Array = values
+            .iter()
+            .map(|value| value.as_deref().map_or(JsValue::NULL, JsValue::from_str))
+            .collect();
+        map.set(&JsValue::from_str(name), &values);
+    }
+    Ok(map.into())
+}
+
+/// Returns a JS `Map` from the content of `key_group` to that of `value_group` over every match,
+/// turning `(\w+)=(\w+)` into a config map. Keys are in order of first appearance. When a key
+/// repeats, the last value wins, or with `collect_duplicates` every key maps to an array of all
+/// its values, in match order. Matches where either group did not participate are skipped.
+#[wasm_bindgen]
+pub fn re_index_by_group(
+    text: &str,
+    pattern: &str,
+    key_group: usize,
+    value_group: usize,
+    flags: &str,
+    collect_duplicates: bool,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let map = Map::new();
+    for (key, values) in index_by_group(&re, text, key_group, value_group, &flags)? {
+        let value: JsValue = if collect_duplicates {
+            values
+                .iter()
+                .map(|value| JsValue::from_str(value))
+                .collect::<Array>()
+                .into()
+        } else {
+            JsValue::from_str(values.last().unwrap())
+        };
+        map.set(&JsValue::from_str(&key), &value);
+    }
+    Ok(map.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The offsets and content of group 0 of each match.
+    fn whole<'a>(matches: &'a MatchSer) -> Vec<(usize, usize, &'a str)> {
+        matches
+            .matches
+            .iter()
+            .map(|m| (m.groups[0].start, m.groups[0].end, &*m.groups[0].content))
+            .collect()
+    }
+
+    #[test]
+    fn find_all_and_find_one_ignore_g() {
+        let re = compile(r"\d", &Flags::default()).unwrap();
+        for flags in ["", "g"] {
+            let flags = Flags::parse(flags);
+            let all = find_all(&re, "1a2b3", &flags);
+            assert_eq!(whole(&all), [(0, 1, "1"), (2, 3, "2"), (4, 5, "3")]);
+            let one = find_one(&re, "1a2b3", &flags);
+            assert_eq!(whole(&one), [(0, 1, "1")]);
+        }
+    }
+
+    #[test]
+    fn nul_bytes_are_matched_like_any_char() {
+        let text = "a\0\0b\0é😀\0";
+        for flags in ["g", "gu"] {
+            let flags = Flags::parse(flags);
+            let re = compile(r"\x00", &flags).unwrap();
+            let matches = find(&re, text, &flags);
+            assert_eq!(
+                whole(&matches),
+                [(1, 2, "\0"), (2, 3, "\0"), (4, 5, "\0"), (11, 12, "\0")]
+            );
+            let utf16: Vec<_> = matches
+                .matches
+                .iter()
+                .map(|m| (m.groups[0].start_utf16, m.groups[0].end_utf16))
+                .collect();
+            assert_eq!(
+                utf16,
+                [
+                    (Some(1), Some(2)),
+                    (Some(2), Some(3)),
+                    (Some(4), Some(5)),
+                    (Some(8), Some(9))
+                ]
+            );
+        }
+    }
+
+    /// The content of each group as sliced from the blob by its UTF-16 `blob_start` and
+    /// `blob_len`.
+    fn packed_contents(packed: &PackedSer) -> Vec<Vec<String>> {
+        let blob: Vec<u16> = packed.content_blob.encode_utf16().collect();
+        packed
+            .matches
+            .iter()
+            .map(|m| {
+                m.groups
+                    .iter()
+                    .map(|g| String::from_utf16(&blob[g.blob_start..][..g.blob_len]).unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn packed_groups_slice_to_their_content() {
+        let flags = Flags::parse("gu");
+        let re = compile(r"(\w)(😀)?", &flags).unwrap();
+        let packed = PackedSer::from(find(&re, "a😀b", &flags));
+        assert_eq!(packed.content_blob, "a😀b");
+        assert_eq!(
+            packed_contents(&packed),
+            [vec!["a😀", "a", "😀"], vec!["b", "b"]]
+        );
+    }
+
+    #[test]
+    fn packed_groups_splitting_a_char_are_appended() {
+        let flags = Flags::default();
+        let re = compile("(.)(.)(.)", &flags).unwrap();
+        let packed = PackedSer::from(find(&re, "€", &flags));
+        assert_eq!(
+            packed_contents(&packed),
+            [vec!["€", "\\xE2", "\\x82", "\\xAC"]]
+        );
+    }
+
+    #[test]
+    fn named_group_occurrences_skip_matches_without_the_group() {
+        let flags = Flags::parse("g");
+        let re = compile(r"(?P<sign>[+-])?(\d+)", &flags).unwrap();
+        let found = named_group_occurrences(&re, "+1 2 😀-3", "sign", &flags).unwrap();
+        let found: Vec<_> = found
+            .iter()
+            .map(|o| (o.match_num, &*o.content, o.start_utf16, o.end_utf16))
+            .collect();
+        assert_eq!(found, [(0, "+", 0, 1), (2, "-", 7, 8)]);
+    }
+
+    #[test]
+    fn named_group_occurrences_reject_unknown_names() {
+        let flags = Flags::default();
+        let re = compile(r"(?P<a>x)", &flags).unwrap();
+        assert!(matches!(
+            named_group_occurrences(&re, "x", "b", &flags),
+            Err(Error::UnknownGroupName { name }) if name == "b"
+        ));
+    }
+
+    #[test]
+    fn s_flag_lets_dot_match_newline() {
+        let flags = Flags::parse("s");
+        let re = compile(".+", &flags).unwrap();
+        assert_eq!(whole(&find(&re, "a\nb", &flags)), [(0, 3, "a\nb")]);
+        let flags = Flags::default();
+        let re = compile(".+", &flags).unwrap();
+        assert_eq!(whole(&find(&re, "a\nb", &flags)), [(0, 1, "a")]);
+    }
+
+    #[test]
+    fn find_bytes_escapes_invalid_utf8() {
+        let flags = Flags::parse("gu");
+        let re = compile(r"(?-u:\xFF)|\w+", &flags).unwrap();
+        let matches = find_bytes(&re, b"ab\xffc\xc3\xa9", &flags);
+        assert_eq!(
+            whole(&matches),
+            [(0, 2, "ab"), (2, 3, "\\xFF"), (3, 6, "cé")]
+        );
+        // Offsets are those of the string decoded with U+FFFD for the invalid byte.
+        let utf16: Vec<_> = matches
+            .matches
+            .iter()
+            .map(|m| (m.groups[0].start_utf16, m.groups[0].end_utf16))
+            .collect();
+        assert_eq!(
+            utf16,
+            [(Some(0), Some(2)), (Some(2), Some(3)), (Some(3), Some(5))]
+        );
+    }
+
+    #[test]
+    fn find_bytes_matches_find_on_valid_utf8() {
+        let flags = Flags::parse("g");
+        let re = compile(r"\S+", &flags).unwrap();
+        let text = "a😀 é";
+        let by_bytes = find_bytes(&re, text.as_bytes(), &flags);
+        assert_eq!(whole(&by_bytes), whole(&find(&re, text, &flags)));
+    }
+
+    #[test]
+    fn sort_nested_puts_parents_first() {
+        let flags = Flags::default();
+        let re = compile("((a)(b))(c)", &flags).unwrap();
+        let mut matches = find(&re, "abc", &flags);
+        matches.sort_nested();
+        let order: Vec<_> = matches.matches[0]
+            .groups
+            .iter()
+            .map(|g| g.group_num)
+            .collect();
+        assert_eq!(order, [0, 1, 2, 3, 4]);
+        let re = compile("(c)?((a)(b))", &flags).unwrap();
+        let mut matches = find(&re, "ab", &flags);
+        matches.sort_nested();
+        let order: Vec<_> = matches.matches[0]
+            .groups
+            .iter()
+            .map(|g| g.group_num)
+            .collect();
+        // Group 0 and group 2 share a span and keep their numbered order.
+        assert_eq!(order, [0, 2, 3, 4]);
+    }
+
+    #[test]
+    fn omit_utf16_drops_the_fields_from_json() {
+        let flags = Flags::parse("g");
+        let re = compile("é+", &flags).unwrap();
+        let mut matches = find(&re, "aéé", &flags);
+        let group = serde_json::to_value(&matches.matches[0].groups[0]).unwrap();
+        assert_eq!(group["start"], 1);
+        assert_eq!(group["end"], 5);
+        assert_eq!(group["start_utf16"], 1);
+        assert_eq!(group["end_utf16"], 3);
+
+        matches.omit_utf16();
+        let group = &matches.matches[0].groups[0];
+        assert_eq!((group.start_utf16, group.end_utf16), (None, None));
+        let json = serde_json::to_value(group).unwrap();
+        let mut keys: Vec<_> = json.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, ["content", "end", "group_num", "name", "start"]);
+    }
+
+    #[test]
+    fn grapheme_len_counts_a_flag_sequence_once() {
+        let flags = Flags::parse("gu");
+        let re = compile(r"\S+", &flags).unwrap();
+        let mut matches = find(&re, "🏴‍☠️ ok", &flags);
+        assert!(matches
+            .matches
+            .iter()
+            .all(|m| m.groups[0].grapheme_len.is_none()));
+        matches.count_graphemes();
+        let pirate = &matches.matches[0].groups[0];
+        assert_eq!(pirate.content.chars().count(), 4);
+        assert_eq!(pirate.grapheme_len, Some(1));
+        assert_eq!(matches.matches[1].groups[0].grapheme_len, Some(2));
+    }
+
+    #[test]
+    fn head_tail_never_repeats_a_match() {
+        let flags = Flags::default();
+        let re = compile(r"\d", &flags).unwrap();
+        let nums = |ms: &[CapturesSer]| ms.iter().map(|m| m.match_num).collect::<Vec<_>>();
+
+        let edges = head_tail(&re, "1 2 3 4 5 6", 2, 2, &flags);
+        assert_eq!(
+            (nums(&edges.head), nums(&edges.tail)),
+            (vec![0, 1], vec![4, 5])
+        );
+        assert_eq!((edges.total, edges.omitted), (6, 2));
+        assert_eq!(edges.tail[1].groups[0].content, "6");
+
+        // Three matches can't fill a head and tail of two each.
+        let overlap = head_tail(&re, "1 2 3", 2, 2, &flags);
+        assert_eq!(
+            (nums(&overlap.head), nums(&overlap.tail)),
+            (vec![0, 1], vec![2])
+        );
+        assert_eq!((overlap.total, overlap.omitted), (3, 0));
+
+        let tail_only = head_tail(&re, "1 2 3", 0, 1, &flags);
+        assert_eq!(
+            (nums(&tail_only.head), nums(&tail_only.tail)),
+            (vec![], vec![2])
+        );
+        assert_eq!(tail_only.omitted, 2);
+    }
+
+    #[test]
+    fn adjacent_to_prev_marks_back_to_back_matches() {
+        let flags = Flags::parse("g");
+        let re = compile(r"ab|c", &flags).unwrap();
+        let matches = find(&re, "abcab ab", &flags);
+        let adjacent: Vec<_> = matches.matches.iter().map(|m| m.adjacent_to_prev).collect();
+        assert_eq!(adjacent, [false, true, true, false]);
+        // Without `g` the only match has nothing before it to touch.
+        let matches = find(&re, "abc", &Flags::default());
+        assert!(!matches.matches[0].adjacent_to_prev);
+    }
+
+    #[test]
+    fn collapse_whitespace_keeps_offsets_and_raw_content() {
+        let flags = Flags::parse("s");
+        let re = compile(r"a.*(b\s+c)", &flags).unwrap();
+        let text = "xa \n\t 1\r\nb \n c";
+        let mut matches = find(&re, text, &flags);
+        let before: Vec<_> = matches.matches[0]
+            .groups
+            .iter()
+            .map(|g| (g.start, g.end))
+            .collect();
+        matches.collapse_whitespace();
+        let groups = &matches.matches[0].groups;
+        assert_eq!(groups[0].content, "a 1 b c");
+        assert_eq!(groups[1].content, "b c");
+        assert_eq!(groups[0].raw_content.as_deref(), Some(&text[1..]));
+        let after: Vec<_> = groups.iter().map(|g| (g.start, g.end)).collect();
+        assert_eq!(before, after);
+        assert_eq!(after, [(1, text.len()), (9, text.len())]);
+    }
+
+    fn layers(pattern: &str, text: &str) -> Vec<(usize, usize)> {
+        let flags = Flags::default();
+        let re = compile(pattern, &flags).unwrap();
+        let mut matches = find(&re, text, &flags);
+        matches.assign_layers();
+        let groups = &matches.matches[0].groups;
+        groups
+            .iter()
+            .map(|g| (g.group_num, g.layer.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn assign_layers_stacks_nested_groups() {
+        assert_eq!(layers("((a(b)))", "ab"), [(0, 0), (1, 1), (2, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn assign_layers_lets_disjoint_groups_share() {
+        // `(a)` and `(bc)` share the layer above the whole match, and `(b)` goes above `(bc)`.
+        assert_eq!(layers("(a)((b)c)", "abc"), [(0, 0), (1, 1), (2, 1), (3, 2)]);
+        // A group starting where another ends doesn't overlap it.
+        assert_eq!(layers("(ab)(c)", "abc"), [(0, 0), (1, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn stats_total_bytes_and_utf16_units() {
+        let flags = Flags::parse("gu");
+        let re = compile(r"\S+", &flags).unwrap();
+        let totals = stats(&re, "😀 é🏴‍☠️ a", &flags);
+        assert_eq!(totals.total_matches, 3);
+        // 4 + (2 + 13) + 1 bytes, 2 + (1 + 5) + 1 units.
+        assert_eq!(totals.total_bytes_matched, 20);
+        assert_eq!(totals.total_utf16_matched, 9);
+        // A match inside a char has an empty UTF-16 span, as `re_find` reports it.
+        let re = compile(r"(?-u:\xF0)", &Flags::parse("g")).unwrap();
+        let totals = stats(&re, "😀", &Flags::parse("g"));
+        assert_eq!(
+            (totals.total_bytes_matched, totals.total_utf16_matched),
+            (1, 0)
+        );
+    }
+
+    #[test]
+    fn html_escape_leaves_offsets_on_the_original() {
+        let flags = Flags::default();
+        let re = compile(r"<script>(.*)</script>", &flags).unwrap();
+        let text = r#"é <script>a&'b"</script>"#;
+        let mut matches = find(&re, text, &flags);
+        matches.collapse_whitespace();
+        matches.html_escape();
+        let groups = &matches.matches[0].groups;
+        assert_eq!(
+            groups[0].content,
+            "&lt;script&gt;a&amp;&#39;b&quot;&lt;/script&gt;"
+        );
+        assert_eq!(groups[1].content, "a&amp;&#39;b&quot;");
+        assert_eq!(groups[1].raw_content.as_deref(), Some("a&amp;&#39;b&quot;"));
+        assert_eq!((groups[0].start, groups[0].end), (3, text.len()));
+        assert_eq!(
+            (groups[1].start_utf16, groups[1].end_utf16),
+            (Some(10), Some(15))
+        );
+        // Content without anything to escape stays borrowed from the input.
+        let mut matches = find(&re, "<script>ok</script>", &flags);
+        matches.html_escape();
+        assert!(matches!(
+            matches.matches[0].groups[1].content,
+            Cow::Borrowed("ok")
+        ));
+    }
+
+    #[test]
+    fn index_by_group_keeps_every_value_of_a_duplicate_key() {
+        let flags = Flags::parse("g");
+        let re = compile(r"(\w+)=(\w+)?", &flags).unwrap();
+        let index = index_by_group(&re, "b=1 a=2 b=3 c= b=4", 1, 2, &flags).unwrap();
+        let index: Vec<_> = index
+            .iter()
+            .map(|(key, values)| (&**key, values.iter().map(|v| &**v).collect::<Vec<_>>()))
+            .collect();
+        // Keys keep their first position; `c` has no value so it is skipped.
+        assert_eq!(index, [("b", vec!["1", "3", "4"]), ("a", vec!["2"])]);
+        // Last-wins, as `re_index_by_group` picks without `collect_duplicates`.
+        let last: Vec<_> = index
+            .iter()
+            .map(|(key, values)| (*key, *values.last().unwrap()))
+            .collect();
+        assert_eq!(last, [("b", "4"), ("a", "2")]);
+    }
+
+    #[test]
+    fn index_by_group_rejects_missing_groups() {
+        let flags = Flags::parse("g");
+        let re = compile(r"(\w+)=(\w+)", &flags).unwrap();
+        let err = index_by_group(&re, "a=1", 1, 3, &flags).unwrap_err();
+        assert!(matches!(err, Error::InvalidGroup { group: 3 }));
+    }
+
+    #[test]
+    fn trim_reports_trimmed_content_without_moving_offsets() {
+        let flags = Flags::default();
+        let re = compile(r"=([^;]*);", &flags).unwrap();
+        let text = "key=  value\u{a0} ;";
+        let mut matches = find(&re, text, &flags);
+        matches.trim();
+        let value = &matches.matches[0].groups[1];
+        assert_eq!(value.content, "  value\u{a0} ");
+        assert_eq!(value.trimmed_content.as_deref(), Some("value"));
+        assert_eq!((value.start, value.end), (4, text.len() - 1));
+        assert!(matches!(value.trimmed_content, Some(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn group_column_marks_missing_groups() {
+        let flags = Flags::parse("g");
+        let re = compile(r"(\d)(é)?", &flags).unwrap();
+        let column = group_column(&re, "1é 2 😀3é", 2, &flags).unwrap();
+        let contents: Vec<_> = column.contents.iter().map(|c| c.as_deref()).collect();
+        assert_eq!(contents, [Some("é"), None, Some("é")]);
+        assert_eq!(column.starts_utf16, [1, -1, 8]);
+        assert_eq!(column.ends_utf16, [2, -1, 9]);
+        assert!(matches!(
+            group_column(&re, "", 3, &flags),
+            Err(Error::InvalidGroup { group: 3 })
+        ));
+    }
+
+    /// Compares `group_column` with picking the group out of full `find` results. Run with
+    /// `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_group_column_against_find() {
+        use std::time::Instant;
+
+        let text: String = (0..200_000).map(|i| format!("k{i}={} ", i % 13)).collect();
+        let flags = Flags::parse("g");
+        let re = compile(r"(\w+)=(\d+)", &flags).unwrap();
+
+        let start = Instant::now();
+        let column = group_column(&re, &text, 2, &flags).unwrap();
+        let by_column = start.elapsed();
+
+        let start = Instant::now();
+        let matches = find(&re, &text, &flags);
+        let from_find: Vec<_> = matches
+            .matches
+            .iter()
+            .map(|m| {
+                m.groups
+                    .iter()
+                    .find(|g| g.group_num == 2)
+                    .map(|g| &g.content)
+            })
+            .collect();
+        let by_find = start.elapsed();
+
+        assert_eq!(column.contents.len(), from_find.len());
+        println!("group_column: {by_column:?}, find: {by_find:?}");
+    }
+
+    #[test]
+    fn reverse_keeps_forward_match_numbers_and_captures() {
+        let flags = Flags::parse("g");
+        let re = compile(r"(\w)(\d)", &flags).unwrap();
+        let options = Options {
+            reverse: true,
+            ..Options::default()
+        };
+        let matches = find_with_options(&re, "a1b2 c3", &flags, &options);
+        let summary: Vec<_> = matches
+            .matches
+            .iter()
+            .map(|m| {
+                (
+                    m.match_num,
+                    m.adjacent_to_prev,
+                    &*m.groups[1].content,
+                    &*m.groups[2].content,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (2, false, "c", "3"),
+                (1, true, "b", "2"),
+                (0, false, "a", "1")
+            ]
+        );
+    }
+
+    #[test]
+    fn score_favors_longer_and_more_complete_matches() {
+        let flags = Flags::parse("g");
+        let re = compile(r"(\d+)(?:\.(\d+))?(?:e(\d+))?", &flags).unwrap();
+        let mut matches = find(&re, "1 12.5 123.45e6 9e1", &flags);
+        matches.score(re.captures_len());
+        let scores: Vec<_> = matches.matches.iter().map(|m| m.score.unwrap()).collect();
+        // Participation is 1/3, 2/3, 3/3 and 2/3, length 1/8, 4/8, 8/8 and 3/8.
+        let expected = [11.0 / 48.0, 28.0 / 48.0, 1.0, 25.0 / 48.0];
+        for (score, expected) in scores.iter().zip(expected) {
+            assert!((score - expected).abs() < 1e-12, "{scores:?}");
+        }
+    }
+
+    #[test]
+    fn score_is_one_for_empty_matches_without_groups() {
+        let flags = Flags::parse("g");
+        let re = compile("x*", &flags).unwrap();
+        let mut matches = find(&re, "ab", &flags);
+        matches.score(re.captures_len());
+        assert!(matches.matches.iter().all(|m| m.score == Some(1.0)));
+    }
+
+    #[test]
+    fn quote_context_flags_matches_in_strings_and_parens() {
+        let flags = Flags::parse("g");
+        let re = compile(r"x\d", &flags).unwrap();
+        let text = r#"x1 "x2 (" x3 (x4 'x5)' "x6") x7 ")x8"#;
+        let mut matches = find(&re, text, &flags);
+        matches.quote_context(text);
+        let context: Vec<_> = matches
+            .matches
+            .iter()
+            .map(|m| (m.in_string.unwrap(), m.in_parens.unwrap()))
+            .collect();
+        // Parens inside strings, like the `(` before x3 and the `)` before x8, don't count.
+        assert_eq!(
+            context,
+            [
+                (false, false),
+                (true, false),
+                (false, false),
+                (false, true),
+                (true, true),
+                (true, true),
+                (false, false),
+                (true, false),
+            ]
+        );
+    }
+
+    fn cluster_spans(text: &str, threshold: usize) -> Vec<(usize, usize, usize)> {
+        let flags = Flags::parse("g");
+        let re = compile(r"x+", &flags).unwrap();
+        let clusters = clusters(&re, text, threshold, &flags);
+        clusters
+            .iter()
+            .map(|c| (c.start_utf16, c.end_utf16, c.match_count))
+            .collect()
+    }
+
+    #[test]
+    fn clusters_split_at_large_gaps() {
+        // Two matches 2 units apart and one 10 units further.
+        let text = "x😀xx..........x";
+        assert_eq!(cluster_spans(text, 5), [(0, 5, 2), (15, 16, 1)]);
+        assert_eq!(cluster_spans(text, 11), [(0, 16, 3)]);
+    }
+
+    #[test]
+    fn clusters_start_anew_at_exactly_the_threshold() {
+        assert_eq!(cluster_spans("x..x", 2), [(0, 1, 1), (3, 4, 1)]);
+        assert_eq!(cluster_spans("x..x", 3), [(0, 4, 2)]);
+        assert_eq!(cluster_spans("xx", 0), [(0, 2, 1)]);
+    }
+
+    #[test]
+    fn encode_offsets_converts_each_encoding() {
+        let (text, flags) = ("é😀x", Flags::default());
+        let re = compile("x", &flags).unwrap();
+        let offsets = |offset_encoding| {
+            let options = Options {
+                offset_encoding,
+                ..Options::default()
+            };
+            let matches = find_with_options(&re, text, &flags, &options);
+            let g = &matches.matches[0].groups[0];
+            (g.start, g.end, g.start_utf16, g.end_utf16)
+        };
+        assert_eq!(offsets(OffsetEncoding::Utf8), (6, 7, Some(3), Some(4)));
+        assert_eq!(offsets(OffsetEncoding::Utf16), (3, 4, Some(3), Some(4)));
+        assert_eq!(offsets(OffsetEncoding::Utf32), (2, 3, Some(3), Some(4)));
+    }
+
+    #[test]
+    fn next_char_is_the_following_grapheme() {
+        let flags = Flags::parse("g");
+        let text = "1e\u{301}2 34";
+        let re = compile(r"\d", &flags).unwrap();
+        let mut matches = find(&re, text, &flags);
+        matches.next_char(text);
+        let next: Vec<_> = matches
+            .matches
+            .iter()
+            .map(|m| m.next_char.clone())
+            .collect();
+        assert_eq!(
+            next,
+            [
+                Some(Some("e\u{301}".to_owned())),
+                Some(Some(" ".to_owned())),
+                Some(Some("4".to_owned())),
+                Some(None),
+            ]
+        );
+    }
+
+    #[test]
+    fn next_char_inside_a_grapheme_is_its_rest() {
+        let flags = Flags::default();
+        let text = "e\u{301}x";
+        let re = compile("e", &flags).unwrap();
+        let mut matches = find(&re, text, &flags);
+        matches.next_char(text);
+        assert_eq!(
+            matches.matches[0].next_char,
+            Some(Some("\u{301}".to_owned()))
+        );
+    }
+
+    #[test]
+    fn find_filtered_by_keeps_matches_the_filter_matches() {
+        let flags = Flags::parse("g");
+        let text = "/home /login /logout /user/login";
+        let re = compile(r"/\S+", &flags).unwrap();
+        let filter = compile("login", &flags).unwrap();
+        let matches = find_filtered_by(&re, &filter, text, &flags);
+        assert_eq!(
+            whole(&matches),
+            [(6, 12, "/login"), (21, 32, "/user/login")]
+        );
+        let match_nums: Vec<_> = matches.matches.iter().map(|m| m.match_num).collect();
+        assert_eq!(match_nums, [1, 3]);
+    }
+
+    #[test]
+    fn find_filtered_by_reports_which_pattern_failed() {
+        let err = re_find_filtered_by("", "(", "a", "").unwrap_err();
+        assert_eq!(err.field, Field::Pattern);
+        let err = re_find_filtered_by("", "a", "(", "").unwrap_err();
+        assert_eq!(err.field, Field::FilterPattern);
+    }
+
+    #[test]
+    fn rank_lengths_shares_ranks_between_ties() {
+        let flags = Flags::parse("g");
+        let re = compile(r"\S+", &flags).unwrap();
+        // `😀` counts 2 UTF-16 units, so `ab😀` ties with `abcd`.
+        let mut matches = find(&re, "a abc ab😀 abcd abc", &flags);
+        matches.rank_lengths();
+        let ranks: Vec<_> = matches.matches.iter().map(|m| m.length_rank).collect();
+        assert_eq!(ranks, [Some(3), Some(2), Some(1), Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn normalize_case_lowercases_every_group() {
+        let flags = Flags::parse("giu");
+        let re = compile(r"(f)oo|(ß)", &flags).unwrap();
+        let mut matches = find(&re, "Foo foo FOO ẞ", &flags);
+        matches.normalize_case();
+        let keys: Vec<_> = matches
+            .matches
+            .iter()
+            .map(|m| {
+                let g = |i: usize| m.groups[i].normalized_content.as_deref();
+                (&*m.groups[0].content, g(0), g(1))
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("Foo", Some("foo"), Some("f")),
+                ("foo", Some("foo"), Some("f")),
+                ("FOO", Some("foo"), Some("f")),
+                ("ẞ", Some("ß"), Some("ß")),
+            ]
+        );
+    }
+
+    #[test]
+    fn size_capped_stops_at_the_budget() {
+        let flags = Flags::parse("g");
+        let re = compile(r"\w+", &flags).unwrap();
+        let text = "é aa bbb c";
+        // Each match is estimated at its length plus 128: 130, 131 and 129 bytes.
+        let capped = size_capped(&re, text, 261, &flags);
+        assert_eq!(whole(&capped.matches), [(3, 5, "aa"), (6, 9, "bbb")]);
+        assert!(capped.truncated);
+        assert_eq!(
+            (capped.resume_from, capped.resume_from_utf16),
+            (Some(10), Some(9))
+        );
+
+        let capped = size_capped(&re, text, 390, &flags);
+        assert_eq!(capped.matches.matches.len(), 3);
+        assert!(!capped.truncated);
+        assert_eq!(capped.resume_from, None);
+    }
+
+    #[test]
+    fn size_capped_counts_every_group_and_may_keep_nothing() {
+        let flags = Flags::parse("g");
+        let re = compile(r"(\w)\w*", &flags).unwrap();
+        // `aa` and its group `a` take 131 bytes.
+        assert_eq!(
+            size_capped(&re, "aa b", 131, &flags).matches.matches.len(),
+            1
+        );
+        let capped = size_capped(&re, "aa b", 130, &flags);
+        assert!(capped.matches.matches.is_empty());
+        assert!(capped.truncated);
+        assert_eq!(capped.resume_from, Some(0));
+    }
+
+    #[test]
+    fn capture_offsets_skip_groups_that_did_not_participate() {
+        let flags = Flags::parse("gu");
+        let re = compile(r"(\w)(é)?", &flags).unwrap();
+        let offsets: Vec<_> = capture_offsets(&re, "aé 😀b", &flags)
+            .iter()
+            .map(|o| {
+                (
+                    o.match_num,
+                    o.group_num,
+                    o.start,
+                    o.end,
+                    o.start_utf16,
+                    o.end_utf16,
+                )
+            })
+            .collect();
+        assert_eq!(
+            offsets,
+            [
+                (0, 0, 0, 3, 0, 2),
+                (0, 1, 0, 1, 0, 1),
+                (0, 2, 1, 3, 1, 2),
+                (1, 0, 8, 9, 5, 6),
+                (1, 1, 8, 9, 5, 6),
+            ]
+        );
+    }
+
+    /// Compares [`capture_offsets`] with [`find`] and serializing its result, as `re_find` does,
+    /// on a large input. Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_capture_offsets_against_find() {
+        use std::time::Instant;
+
+        let text: String = (0..200_000)
+            .map(|i| format!("k{i}={} é ", i % 13))
+            .collect();
+        let flags = Flags::parse("g");
+        let re = compile(r"(\w+)=(\d+)", &flags).unwrap();
+
+        let start = Instant::now();
+        let offsets = capture_offsets(&re, &text, &flags);
+        let by_offsets = start.elapsed();
+
+        let start = Instant::now();
+        let matches = find(&re, &text, &flags);
+        let json = serde_json::to_string(&matches).unwrap();
+        let by_find = start.elapsed();
+
+        let groups: usize = matches.matches.iter().map(|m| m.groups.len()).sum();
+        assert_eq!(offsets.len(), groups);
+        println!(
+            "capture_offsets: {by_offsets:?}, find: {by_find:?} ({} bytes of JSON)",
+            json.len()
+        );
+    }
+}
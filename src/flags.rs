@@ -0,0 +1,86 @@
+use regex::bytes::RegexBuilder;
+use regex_syntax::{ast, Parser, ParserBuilder};
+
+/// Matching options parsed from a JS-style flag string such as `"gi"`. Unrecognized characters
+/// are ignored.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Flags {
+    /// `g`: report every match instead of only the first one.
+    pub global: bool,
+    /// `i`
+    pub case_insensitive: bool,
+    /// `m`: `^` and `$` match at line boundaries.
+    pub multi_line: bool,
+    /// `s`: `.` matches `\n`.
+    pub dot_matches_new_line: bool,
+    /// `u`: Unicode-aware classes and case folding. Without it patterns match bytes, and
+    /// matches that are not valid UTF-8 are reported with `\x..` escapes.
+    pub unicode: bool,
+    /// `x`
+    pub ignore_whitespace: bool,
+    /// `U`: quantifiers are lazy by default and `?` makes them greedy.
+    pub swap_greed: bool,
+    /// `R`: with `m`, `^` and `$` also treat `\r\n` as a line boundary, and never match between
+    /// its `\r` and `\n`. Without `s`, `.` doesn't match `\r` either.
+    pub crlf: bool,
+    /// `w`: `\w`, `\W` and word boundaries such as `\b` only treat ASCII letters, digits and `_`
+    /// as word chars, even with `u`. Unlike leaving out `u`, which makes the whole pattern match
+    /// bytes, literals and other classes stay Unicode-aware.
+    pub ascii_word: bool,
+}
+
+impl Flags {
+    pub fn parse(flags: &str) -> Flags {
+        let mut parsed = Flags::default();
+        for flag in flags.chars() {
+            match flag {
+                'g' => parsed.global = true,
+                'i' => parsed.case_insensitive = true,
+                'm' => parsed.multi_line = true,
+                's' => parsed.dot_matches_new_line = true,
+                'u' => parsed.unicode = true,
+                'x' => parsed.ignore_whitespace = true,
+                'U' => parsed.swap_greed = true,
+                'R' => parsed.crlf = true,
+                'w' => parsed.ascii_word = true,
+                _ => {}
+            }
+        }
+        parsed
+    }
+
+    /// A regex-syntax parser configured the same way as [`Flags::builder`], used to get
+    /// structured syntax errors.
+    pub fn parser(&self) -> Parser {
+        ParserBuilder::new()
+            .utf8(false)
+            .case_insensitive(self.case_insensitive)
+            .multi_line(self.multi_line)
+            .dot_matches_new_line(self.dot_matches_new_line)
+            .unicode(self.unicode)
+            .ignore_whitespace(self.ignore_whitespace)
+            .swap_greed(self.swap_greed)
+            .crlf(self.crlf)
+            .build()
+    }
+
+    /// A parser for the AST of a pattern, which only depends on the `x` flag.
+    pub fn ast_parser(&self) -> ast::parse::Parser {
+        ast::parse::ParserBuilder::new()
+            .ignore_whitespace(self.ignore_whitespace)
+            .build()
+    }
+
+    pub fn builder(&self, pattern: &str) -> RegexBuilder {
+        let mut builder = RegexBuilder::new(pattern);
+        builder
+            .case_insensitive(self.case_insensitive)
+            .multi_line(self.multi_line)
+            .dot_matches_new_line(self.dot_matches_new_line)
+            .unicode(self.unicode)
+            .ignore_whitespace(self.ignore_whitespace)
+            .swap_greed(self.swap_greed)
+            .crlf(self.crlf);
+        builder
+    }
+}
@@ -0,0 +1,171 @@
+use wasm_bindgen::prelude::*;
+
+use crate::flags::Flags;
+use crate::{compile, Error};
+
+fn escape(c: char) -> String {
+    regex::escape(c.encode_utf8(&mut [0; 4]))
+}
+
+/// Translates the class of `glob` whose `[` ends at byte `start`, returning it with the offset
+/// just past its `]`, or `None` if it is never closed.
+fn class(glob: &str, start: usize) -> Option<(String, usize)> {
+    let rest = &glob[start..];
+    let (negated, body_start) = match rest.chars().next() {
+        Some('!' | '^') => (true, 1),
+        _ => (false, 0),
+    };
+    // A `]` right after the opening is part of the class.
+    let first_len = rest[body_start..].chars().next()?.len_utf8();
+    let body_end = body_start + first_len + rest[body_start + first_len..].find(']')?;
+    let body: Vec<char> = rest[body_start..body_end].chars().collect();
+    // Classes are Unicode-aware so they match whole chars without the `u` flag too.
+    let mut class = String::from(if negated { "(?u:[^/" } else { "(?u:[" });
+    // Every char is escaped, as `--`, `&&` and `~~` are set operators in a regex class. A `-` is
+    // a range only between two chars that are not `-`, and one right after a range is literal.
+    let mut i = 0;
+    while i < body.len() {
+        match (body[i], body.get(i + 1), body.get(i + 2)) {
+            (start, Some('-'), Some(&end)) if start != '-' && end != '-' => {
+                class.push_str(&escape(start));
+                class.push('-');
+                class.push_str(&escape(end));
+                i += 3;
+            }
+            (c, ..) => {
+                class.push_str(&escape(c));
+                i += 1;
+            }
+        }
+    }
+    class.push_str("])");
+    Some((class, start + body_end + 1))
+}
+
+pub(crate) fn translate(glob: &str) -> String {
+    let mut regex = String::from(r"\A");
+    let mut chars = glob.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '*' if chars.next_if(|&(_, c)| c == '*').is_some() => {
+                let segment_start = i == 0 || glob[..i].ends_with('/');
+                if segment_start && chars.next_if(|&(_, c)| c == '/').is_some() {
+                    regex.push_str("(?u:[^/]*/)*");
+                } else if segment_start && chars.peek().is_none() {
+                    regex.push_str("(?s:.*)");
+                } else {
+                    regex.push_str("(?u:[^/]*)");
+                }
+            }
+            '*' => regex.push_str("(?u:[^/]*)"),
+            '?' => regex.push_str("(?u:[^/])"),
+            '[' => match class(glob, i + 1) {
+                Some((class, end)) => {
+                    regex.push_str(&class);
+                    while chars.next_if(|&(j, _)| j < end).is_some() {}
+                }
+                None => regex.push_str(r"\["),
+            },
+            '\\' => match chars.next() {
+                Some((_, escaped)) => regex.push_str(&escape(escaped)),
+                None => regex.push_str(r"\\"),
+            },
+            c => regex.push_str(&escape(c)),
+        }
+    }
+    regex.push_str(r"\z");
+    regex
+}
+
+/// Translates the path glob `glob` into a pattern matching the same paths, anchored at both
+/// ends, so `*.rs` matches `main.rs` but not `main.rsx`. The supported dialect is that of shell
+/// and `.gitignore` style globs, with `/` as the only separator:
+///
+/// - `*` matches any run of chars but `/`, and `?` any one char but `/`. Both match a leading
+///   `.`, unlike in shells.
+/// - `**` as a whole path segment matches any number of segments: `**/` matches zero or more
+///   directories and a trailing `/**` everything below. Elsewhere it is the same as `*`.
+/// - `[abc]`, `[a-z]` and the negations `[!a-z]` and `[^a-z]` match one char of, or not of,
+///   the class. Negated classes never match `/`. A `]` right after the opening is literal, as is
+///   a `-` that doesn't sit between two other chars, and a `[` that is never closed matches
+///   itself.
+/// - Wildcards and classes match whole chars, with or without the `u` flag, so `?` matches `é`
+///   and `[é]` compiles.
+/// - `\` makes the char after it literal.
+///
+/// Brace expansion such as `{a,b}` is not supported, braces match themselves. With `validate`,
+/// the pattern is also compiled with `flags`, such as `i` for case-insensitive paths, and its
+/// errors returned.
+#[wasm_bindgen]
+pub fn glob_to_regex(glob: &str, flags: &str, validate: bool) -> Result<String, Error> {
+    let pattern = translate(glob);
+    if validate {
+        compile(&pattern, &Flags::parse(flags))?;
+    }
+    Ok(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob_matches(glob: &str, path: &str) -> bool {
+        let pattern = glob_to_regex(glob, "", true).unwrap();
+        compile(&pattern, &Flags::default())
+            .unwrap()
+            .is_match(path.as_bytes())
+    }
+
+    #[test]
+    fn star_stays_in_its_segment() {
+        assert!(glob_matches("*.rs", "main.rs"));
+        assert!(!glob_matches("*.rs", "main.rsx"));
+        assert!(!glob_matches("*.rs", "src/main.rs"));
+        assert!(glob_matches("src/**/*.rs", "src/main.rs"));
+        assert!(glob_matches("src/**/*.rs", "src/a/b/main.rs"));
+        assert!(glob_matches("src/**", "src/a/b"));
+        assert!(!glob_matches("a**", "ab/c"));
+        assert!(glob_matches("fo?", "foo"));
+        assert!(glob_matches(r"\*", "*"));
+        assert!(!glob_matches(r"\*", "a"));
+    }
+
+    #[test]
+    fn classes_escape_set_operators() {
+        assert_eq!(translate("[a--b]"), r"\A(?u:[a\-\-b])\z");
+        assert_eq!(translate("[a-c]"), r"\A(?u:[a-c])\z");
+        assert!(glob_matches("[a--b]", "-"));
+        assert!(!glob_matches("[a--b]", "c"));
+        assert!(!glob_matches("[+--]", ","));
+        assert!(glob_matches("[&&x]", "&"));
+        assert!(glob_matches("[~~x]", "~"));
+        assert!(!glob_matches("[~~x]", "y"));
+        assert!(glob_matches("[a-c-e]", "-"));
+        assert!(!glob_matches("[a-c-e]", "d"));
+    }
+
+    #[test]
+    fn negated_classes_never_match_a_separator() {
+        assert!(glob_matches("a[!b]c", "axc"));
+        assert!(!glob_matches("a[!b]c", "abc"));
+        assert!(!glob_matches("a[^b]c", "a/c"));
+        assert!(glob_matches("[]]", "]"));
+        assert!(glob_matches("[", "["));
+    }
+
+    #[test]
+    fn wildcards_and_classes_match_whole_chars() {
+        assert_eq!(translate("fo?"), r"\Afo(?u:[^/])\z");
+        assert!(glob_matches("fo?", "foé"));
+        assert!(glob_matches("fo?", "fo😀"));
+        assert!(!glob_matches("fo?", "foé!"));
+        assert!(glob_matches("*.rs", "café.rs"));
+        assert!(glob_matches("**/*.rs", "répertoire/ñ/a.rs"));
+        assert!(glob_matches("[é]", "é"));
+        assert!(glob_matches("[à-ü]x", "éx"));
+        assert!(!glob_matches("[à-ü]", "a"));
+        assert!(glob_matches("a[!b]c", "aéc"));
+        assert!(!glob_matches("a[!é]c", "aéc"));
+        assert!(glob_to_regex("[é]", "i", true).is_ok());
+    }
+}
@@ -1,8 +1,178 @@
-use regex::Regex;
+mod analysis;
+mod batch;
+mod compiled;
+mod diagnostics;
+mod error;
+mod example;
+mod extract;
+mod find;
+mod flags;
+mod glob;
+mod lines;
+mod options;
+mod render;
+mod replace;
+mod spans;
+mod stream;
+mod tree;
+mod utf16;
+
+use regex::bytes::{Regex, RegexBuilder};
+use serde::Serialize;
 use wasm_bindgen::prelude::*;
 
+pub use compiled::CompiledRegex;
+pub use error::{Error, Field, FieldError, Phase, ReSyntax};
+pub use find::{CapSer, CapturesSer, MatchSer};
+pub use flags::Flags;
+pub use options::Options;
+pub use stream::StreamingReplacer;
+
+/// Compiles `pattern`, parsing it with regex-syntax first so syntax errors come back
+/// structured rather than as a pre-rendered string.
+fn compile(pattern: &str, flags: &Flags) -> Result<Regex, Error> {
+    Ok(compile_builder(pattern, flags)?.build()?)
+}
+
+/// The builder [`compile`] builds, for callers that need to configure it further.
+fn compile_builder(pattern: &str, flags: &Flags) -> Result<RegexBuilder, Error> {
+    flags.parser().parse(pattern)?;
+    if flags.ascii_word {
+        return Ok(flags.builder(&analysis::ascii_word(pattern, flags)?));
+    }
+    Ok(flags.builder(pattern))
+}
+
+/// [`compile`] with the compile-time guards of `options` applied.
+fn compile_with(pattern: &str, flags: &Flags, options: &Options) -> Result<Regex, Error> {
+    let flags = &Flags {
+        crlf: flags.crlf || options.crlf,
+        ..*flags
+    };
+    if options.reject_empty_pattern && pattern.is_empty() {
+        return Err(Error::EmptyPattern);
+    }
+    if options.whole_word {
+        // Report errors against the pattern as written, wrapping it shifts the offsets.
+        flags.parser().parse(pattern)?;
+        return compile(&whole_word(pattern, flags)?, flags);
+    }
+    compile(pattern, flags)
+}
+
+/// `pattern` wrapped in word boundaries. A trailing `x` mode comment is terminated first, or it
+/// would swallow the closing paren.
+fn whole_word(pattern: &str, flags: &Flags) -> Result<String, Error> {
+    let parsed = flags
+        .ast_parser()
+        .parse_with_comments(pattern)
+        .map_err(regex_syntax::Error::from)?;
+    let ends_in_comment = parsed
+        .comments
+        .last()
+        .is_some_and(|comment| comment.span.end.offset == pattern.len());
+    let newline = if ends_in_comment { "\n" } else { "" };
+    Ok(format!("\\b(?:{}{})\\b", pattern, newline))
+}
+
+pub(crate) fn to_js<T: Serialize + ?Sized>(value: &T) -> JsValue {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .unwrap_throw()
+}
+
 #[wasm_bindgen]
-pub fn test(text: &str, reg_exp: &str) -> bool {
-    let re = Regex::new(reg_exp).unwrap();
-    re.is_match(text)
+pub fn test(text: &str, reg_exp: &str) -> Result<bool, Error> {
+    let flags = Flags {
+        unicode: true,
+        ..Flags::default()
+    };
+    let re = compile(reg_exp, &flags)?;
+    Ok(re.is_match(text.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_pattern_is_allowed_by_default() {
+        let re = compile_with("", &Flags::default(), &Options::default()).unwrap();
+        assert!(re.is_match(b"abc"));
+    }
+
+    #[test]
+    fn empty_pattern_is_rejected_with_the_option() {
+        let options = Options {
+            reject_empty_pattern: true,
+            ..Options::default()
+        };
+        let result = compile_with("", &Flags::default(), &options);
+        assert!(matches!(result, Err(Error::EmptyPattern)));
+        // Only the empty pattern is rejected, not patterns that match the empty string.
+        assert!(compile_with("a*", &Flags::default(), &options).is_ok());
+    }
+
+    fn whole_word_matches(text: &str, pattern: &str, flags: &str) -> Vec<(usize, usize)> {
+        let flags = Flags::parse(flags);
+        let options = Options {
+            whole_word: true,
+            ..Options::default()
+        };
+        let re = compile_with(pattern, &flags, &options).unwrap();
+        re.find_iter(text.as_bytes())
+            .map(|m| (m.start(), m.end()))
+            .collect()
+    }
+
+    #[test]
+    fn whole_word_skips_partial_words() {
+        assert_eq!(
+            whole_word_matches("cats category cat", "cat", ""),
+            [(14, 17)]
+        );
+    }
+
+    #[test]
+    fn whole_word_composes_with_flags_and_alternation() {
+        assert_eq!(
+            whole_word_matches("Cat dog cats", "cat|dog", "i"),
+            [(0, 3), (4, 7)]
+        );
+        // The boundary follows the `u` flag's word chars.
+        assert_eq!(whole_word_matches("écat cat", "cat", "u"), [(6, 9)]);
+        assert_eq!(
+            whole_word_matches("cat # c", "cat # comment", "x"),
+            [(0, 3)]
+        );
+    }
+
+    fn positions(pattern: &str, flags: &str, text: &str) -> Vec<usize> {
+        let re = compile(pattern, &Flags::parse(flags)).unwrap();
+        re.find_iter(text.as_bytes()).map(|m| m.start()).collect()
+    }
+
+    #[test]
+    fn crlf_flag_treats_crlf_as_one_line_break() {
+        let text = "a\r\nb\rc\n";
+        // `$` before the `\r` rather than between `\r` and `\n`, and a lone `\r` ends a line too.
+        assert_eq!(positions("$", "m", text), [2, 6, 7]);
+        assert_eq!(positions("$", "mR", text), [1, 4, 6, 7]);
+        assert_eq!(positions("^", "m", text), [0, 3, 7]);
+        assert_eq!(positions("^", "mR", text), [0, 3, 5, 7]);
+        // Without `m` the flag changes nothing but `.`.
+        assert_eq!(positions("$", "R", text), [7]);
+        assert_eq!(positions(".", "R", "a\rb"), [0, 2]);
+    }
+
+    #[test]
+    fn crlf_option_is_the_same_as_the_flag() {
+        let options = Options {
+            crlf: true,
+            ..Options::default()
+        };
+        let re = compile_with("^b", &Flags::parse("m"), &options).unwrap();
+        assert!(re.is_match(b"a\rb"));
+        assert!(!compile("^b", &Flags::parse("m")).unwrap().is_match(b"a\rb"));
+    }
 }
@@ -0,0 +1,427 @@
+use regex::bytes::Regex;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::find::{captures_iter, find, CapturesSer};
+use crate::flags::Flags;
+use crate::utf16::{byte_index_utf16, utf16_index_bytes, Utf16Cursor};
+use crate::{compile, to_js, Error};
+
+/// A line of the input, excluding its `\n` or `\r\n` terminator. `line` is 1-based.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LineSer {
+    pub line: usize,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+}
+
+/// The lines of `text` as split by `str::lines`: a trailing newline ends the last line rather
+/// than starting an empty one.
+pub(crate) fn lines(text: &str) -> Vec<LineSer> {
+    let mut cursor = Utf16Cursor::new(text);
+    let mut start_byte = 0;
+    text.split_inclusive('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            let next = start_byte + line.len();
+            let content = line.strip_suffix('\n').unwrap_or(line);
+            let content = content.strip_suffix('\r').unwrap_or(content);
+            let end_byte = start_byte + content.len();
+            let line = LineSer {
+                line: i + 1,
+                start_byte,
+                end_byte,
+                start_utf16: cursor.utf16(start_byte),
+                end_utf16: cursor.utf16(end_byte),
+            };
+            start_byte = next;
+            line
+        })
+        .collect()
+}
+
+/// The UTF-16 offset just after each `\n` of `text`.
+pub(crate) fn newline_offsets(text: &str) -> Vec<usize> {
+    let mut cursor = Utf16Cursor::new(text);
+    text.match_indices('\n')
+        .map(|(i, _)| cursor.utf16(i + 1))
+        .collect()
+}
+
+/// A position in the input in both UTF-8 bytes and UTF-16 code units.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct OffsetSer {
+    pub byte: usize,
+    pub utf16: usize,
+}
+
+/// The offset of the 1-based `line` and 0-based UTF-16 `column_utf16` of `text`, if both are in
+/// range.
+pub(crate) fn line_col_offset(text: &str, line: usize, column_utf16: usize) -> Option<OffsetSer> {
+    let lines = lines(text);
+    let line = match lines.get(line.checked_sub(1)?) {
+        Some(line) => *line,
+        // The empty line after a trailing newline, or of an empty text.
+        None if line == lines.len() + 1 && (text.is_empty() || text.ends_with('\n')) => {
+            let utf16 = text.encode_utf16().count();
+            LineSer {
+                line,
+                start_byte: text.len(),
+                end_byte: text.len(),
+                start_utf16: utf16,
+                end_utf16: utf16,
+            }
+        }
+        None => return None,
+    };
+    if column_utf16 > line.end_utf16 - line.start_utf16 {
+        return None;
+    }
+    let content = &text[line.start_byte..line.end_byte];
+    let byte = byte_index_utf16(content, column_utf16);
+    Some(OffsetSer {
+        byte: line.start_byte + byte,
+        utf16: line.start_utf16 + utf16_index_bytes(content, byte),
+    })
+}
+
+/// The index in `lines` of the line containing byte `offset`, counting a line terminator as
+/// part of the line it ends.
+pub(crate) fn line_index(lines: &[LineSer], offset: usize) -> usize {
+    lines
+        .partition_point(|line| line.start_byte <= offset)
+        .saturating_sub(1)
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchingLineSer<'a> {
+    #[serde(flatten)]
+    pub line: LineSer,
+    pub content: &'a str,
+    /// How many matches start on, end on or span this line.
+    pub match_count: usize,
+}
+
+pub(crate) fn matching_lines<'a>(
+    re: &Regex,
+    text: &'a str,
+    flags: &Flags,
+) -> Vec<MatchingLineSer<'a>> {
+    let lines = lines(text);
+    if lines.is_empty() {
+        return Vec::new();
+    }
+    // Matches come in order, so the lines they touch only ever repeat the last one counted.
+    let mut counts: Vec<(usize, usize)> = Vec::new();
+    for caps in captures_iter(re, text, 0, flags) {
+        let m = caps.get(0).unwrap();
+        let first = line_index(&lines, m.start());
+        let last = line_index(&lines, m.end().saturating_sub(1).max(m.start()));
+        for i in first..=last {
+            match counts.last_mut() {
+                Some((line, count)) if *line == i => *count += 1,
+                _ => counts.push((i, 1)),
+            }
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(i, match_count)| {
+            let line = lines[i];
+            MatchingLineSer {
+                content: &text[line.start_byte..line.end_byte],
+                line,
+                match_count,
+            }
+        })
+        .collect()
+}
+
+/// A sentence of the input, without the whitespace around it.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SentenceSer<'a> {
+    pub content: &'a str,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+}
+
+/// Splits `text` into at least one sentence, each paired with the offset where the text it
+/// covers starts, including the whitespace before it. A sentence ends after a run of `.`, `!` or
+/// `?` followed by whitespace or the end of the text, or at a `\n`. `3.5` is not split, but
+/// abbreviations such as `e.g.` do end a sentence. Sentences are only empty if all of `text` is
+/// whitespace.
+fn sentences(text: &str) -> Vec<(usize, SentenceSer<'_>)> {
+    let bytes = text.as_bytes();
+    let mut cursor = Utf16Cursor::new(text);
+    let mut sentences = Vec::new();
+    let mut push = |region_start: usize, region_end: usize| {
+        let region = &text[region_start..region_end];
+        let content = region.trim();
+        let start_byte = region_start + (region.len() - region.trim_start().len());
+        let end_byte = start_byte + content.len();
+        let sentence = SentenceSer {
+            content,
+            start_byte,
+            end_byte,
+            start_utf16: cursor.utf16(start_byte),
+            end_utf16: cursor.utf16(end_byte),
+        };
+        sentences.push((region_start, sentence));
+    };
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\n' => {
+                // Blank lines are left to the whitespace before the next sentence.
+                if !text[start..i].trim().is_empty() {
+                    push(start, i);
+                    start = i + 1;
+                }
+                i += 1;
+            }
+            b'.' | b'!' | b'?' => {
+                while i < bytes.len() && matches!(bytes[i], b'.' | b'!' | b'?') {
+                    i += 1;
+                }
+                if bytes.get(i).is_none_or(u8::is_ascii_whitespace) {
+                    push(start, i);
+                    start = i;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    if start == 0 || !text[start..].trim().is_empty() {
+        push(start, bytes.len());
+    }
+    sentences
+}
+
+#[derive(Debug, Serialize)]
+pub struct SentenceMatchSer<'a> {
+    #[serde(flatten)]
+    pub captures: CapturesSer<'a>,
+    pub sentence: SentenceSer<'a>,
+}
+
+pub(crate) fn find_with_sentences<'a>(
+    re: &'a Regex,
+    text: &'a str,
+    flags: &Flags,
+) -> Vec<SentenceMatchSer<'a>> {
+    let sentences = sentences(text);
+    find(re, text, flags)
+        .matches
+        .into_iter()
+        .map(|captures| {
+            let start = captures.groups[0].start;
+            let i = sentences.partition_point(|(region_start, _)| *region_start <= start) - 1;
+            SentenceMatchSer {
+                captures,
+                sentence: sentences[i].1,
+            }
+        })
+        .collect()
+}
+
+/// Returns the byte and UTF-16 boundaries of every line of `text`, see [`LineSer`].
+#[wasm_bindgen]
+pub fn line_offsets(text: &str) -> JsValue {
+    to_js(&lines(text))
+}
+
+/// Returns `{ byte, utf16 }`, the offset of the 1-based `line` and 0-based UTF-16 `column_utf16`
+/// of `text` as editors report positions, or `null` if the line doesn't exist or is shorter
+/// than the column. Lines are those of [`line_offsets`], plus the empty line after a trailing
+/// newline, and the column may be the end of the line. A column between the two halves of a
+/// surrogate pair is floored to the start of the char.
+#[wasm_bindgen]
+pub fn line_col_to_offset(text: &str, line: usize, column_utf16: usize) -> JsValue {
+    to_js(&line_col_offset(text, line, column_utf16))
+}
+
+/// Returns the UTF-16 offset just after every `\n` of `text`, the start of each line but the
+/// first, so the line of an offset can be found by binary search. A `\r\n` counts once, after
+/// its `\n`, and a trailing newline still reports the offset at the end of the text.
+#[wasm_bindgen]
+pub fn newline_offsets_utf16(text: &str) -> Vec<usize> {
+    newline_offsets(text)
+}
+
+/// Returns every line of `text` touched by a match, like grep, with the number of matches on
+/// it. A match spanning several lines reports all of them.
+#[wasm_bindgen]
+pub fn re_matching_lines(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&matching_lines(&re, text, &flags)))
+}
+
+/// Like `re_find`, but returns a flat array of matches, each with the `sentence` containing its
+/// start as `{ content, start_byte, end_byte, start_utf16, end_utf16 }`. Sentences are split
+/// heuristically: one ends after a run of `.`, `!` or `?` followed by whitespace or the end of
+/// the text, or at a line break, and whitespace around it is trimmed. Abbreviations like
+/// `e.g.` are not recognized.
+#[wasm_bindgen]
+pub fn re_find_with_sentences(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&find_with_sentences(&re, text, &flags)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The byte and UTF-16 bounds of each line.
+    fn bounds(text: &str) -> Vec<(usize, usize, usize, usize)> {
+        lines(text)
+            .iter()
+            .map(|l| (l.start_byte, l.end_byte, l.start_utf16, l.end_utf16))
+            .collect()
+    }
+
+    #[test]
+    fn lines_exclude_crlf_and_lf_terminators() {
+        assert_eq!(
+            bounds("ab\r\nc\nd"),
+            [(0, 2, 0, 2), (4, 5, 4, 5), (6, 7, 6, 7)]
+        );
+    }
+
+    #[test]
+    fn trailing_newline_ends_the_last_line() {
+        assert_eq!(bounds("ab\n"), [(0, 2, 0, 2)]);
+        assert_eq!(bounds("ab\n\n"), [(0, 2, 0, 2), (3, 3, 3, 3)]);
+        assert!(bounds("").is_empty());
+    }
+
+    #[test]
+    fn line_offsets_count_utf16_separately() {
+        let lines = lines("😀\né");
+        assert_eq!(lines[1].line, 2);
+        assert_eq!(bounds("😀\né"), [(0, 4, 0, 2), (5, 7, 3, 4)]);
+    }
+
+    fn grep(text: &str, pattern: &str) -> Vec<(usize, String, usize)> {
+        let flags = Flags::parse("g");
+        let re = compile(pattern, &flags).unwrap();
+        matching_lines(&re, text, &flags)
+            .into_iter()
+            .map(|l| (l.line.line, l.content.to_string(), l.match_count))
+            .collect()
+    }
+
+    #[test]
+    fn matching_lines_count_matches_per_line() {
+        let found = grep("a1 b2\nnone\nc3\r\n", r"\d");
+        assert_eq!(found, [(1, "a1 b2".into(), 2), (3, "c3".into(), 1)]);
+    }
+
+    #[test]
+    fn matching_lines_report_every_line_a_match_spans() {
+        let found = grep("x\nab\ncd\ny", r"b\ncd\n");
+        assert_eq!(found, [(2, "ab".into(), 1), (3, "cd".into(), 1)]);
+    }
+
+    #[test]
+    fn sentences_split_on_punctuation_and_newlines() {
+        let text = "Hi there! Is 3.5 ok?? Yes...\nnext line é";
+        let contents: Vec<_> = sentences(text).iter().map(|(_, s)| s.content).collect();
+        assert_eq!(
+            contents,
+            ["Hi there!", "Is 3.5 ok??", "Yes...", "next line é"]
+        );
+        let (region_start, second) = sentences(text)[1];
+        assert_eq!(
+            (region_start, second.start_byte, second.end_byte),
+            (9, 10, 21)
+        );
+        assert_eq!(sentences("").len(), 1);
+    }
+
+    #[test]
+    fn find_with_sentences_reports_the_sentence_of_each_match() {
+        let flags = Flags::parse("g");
+        let re = compile(r"\d+", &flags).unwrap();
+        let text = "One 1. Two 2 and 22!\n😀 3";
+        let found: Vec<_> = find_with_sentences(&re, text, &flags)
+            .iter()
+            .map(|m| (m.captures.groups[0].content.to_string(), m.sentence.content))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("1".to_string(), "One 1."),
+                ("2".to_string(), "Two 2 and 22!"),
+                ("22".to_string(), "Two 2 and 22!"),
+                ("3".to_string(), "😀 3"),
+            ]
+        );
+        let last = find_with_sentences(&re, text, &flags)
+            .pop()
+            .unwrap()
+            .sentence;
+        assert_eq!((last.start_utf16, last.end_utf16), (21, 25));
+    }
+
+    #[test]
+    fn sentences_are_never_blank() {
+        let contents = |text| {
+            sentences(text)
+                .iter()
+                .map(|(_, s)| s.content)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(contents("a.\n\n b \n"), ["a.", "b"]);
+        assert_eq!(contents("a. "), ["a."]);
+        assert_eq!(contents(" \n "), [""]);
+    }
+
+    #[test]
+    fn newline_offsets_count_crlf_once() {
+        assert_eq!(newline_offsets("a\r\n😀\nb\r\n"), [3, 6, 9]);
+        assert_eq!(newline_offsets("\n\n"), [1, 2]);
+        // A lone `\r` doesn't end a line.
+        assert_eq!(newline_offsets("a\rb"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn line_col_offset_counts_columns_in_utf16() {
+        let text = "ab\r\né😀x\n";
+        let offset = |line, col| line_col_offset(text, line, col).map(|o| (o.byte, o.utf16));
+        assert_eq!(offset(1, 2), Some((2, 2)));
+        assert_eq!(offset(2, 3), Some((10, 7)));
+        // Between the halves of the emoji floors to its start.
+        assert_eq!(offset(2, 2), Some((6, 5)));
+        assert_eq!(offset(2, 4), Some((11, 8)));
+        assert_eq!(offset(2, 5), None);
+        // The empty line after the trailing newline.
+        assert_eq!(offset(3, 0), Some((12, 9)));
+        assert_eq!(offset(3, 1), None);
+        assert_eq!(offset(4, 0), None);
+        assert_eq!(offset(0, 0), None);
+        assert_eq!(line_col_offset("", 1, 0).map(|o| o.byte), Some(0));
+    }
+
+    #[test]
+    fn line_col_offset_round_trips_char_boundaries() {
+        let text = "one\né😀\r\n\nlast";
+        let lines = lines(text);
+        for (byte, _) in text.char_indices() {
+            let line = lines[line_index(&lines, byte)];
+            if byte > line.end_byte {
+                // Inside a `\r\n` terminator, which no column reaches.
+                continue;
+            }
+            let utf16 = utf16_index_bytes(text, byte);
+            let offset = line_col_offset(text, line.line, utf16 - line.start_utf16).unwrap();
+            assert_eq!((offset.byte, offset.utf16), (byte, utf16), "at {byte}");
+        }
+    }
+}
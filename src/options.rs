@@ -0,0 +1,88 @@
+use serde::Deserialize;
+use wasm_bindgen::JsValue;
+
+use crate::Error;
+
+/// Options passed as a JS object to the `*_with_options` functions. Missing fields, or a
+/// missing object, take the defaults.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    /// Fail with `Error::EmptyPattern` instead of compiling an empty pattern, which matches the
+    /// empty string at every position of the input.
+    pub reject_empty_pattern: bool,
+    /// Only match whole words by wrapping the pattern in `\b(?:...)\b`, with `\b` following
+    /// the `u` flag's definition of a word char. Offsets are unaffected as `\b` is zero-width.
+    pub whole_word: bool,
+    /// Only used when replacing: return just the expanded replacements, one after the other,
+    /// and drop the text between matches.
+    pub only_matches: bool,
+    /// Leave `start_utf16` and `end_utf16` out of match results, for callers that only index
+    /// the input by UTF-8 bytes.
+    pub omit_utf16: bool,
+    /// Treat `\r\n` as a line terminator like `\n`, the same as the `R` flag.
+    pub crlf: bool,
+    /// Replace every run of whitespace in match content with a single space, for compact
+    /// display. The content as matched is kept in `raw_content` and offsets are unaffected.
+    pub collapse_whitespace: bool,
+    /// Escape `<`, `>`, `&`, `"` and `'` in match content, so it can be inserted into HTML as
+    /// is. Offsets still refer to the unescaped input.
+    pub html_escape: bool,
+    /// Also report the content of each group without leading and trailing whitespace as
+    /// `trimmed_content`, after `collapse_whitespace` if both are set. Offsets are unaffected.
+    pub trim: bool,
+    /// Only used when finding: report the matches last to first, for callers that edit the
+    /// input from the end so earlier offsets stay valid. `match_num` and `adjacent_to_prev` still
+    /// follow the forward scan. The regex crate only scans forward, so every match is collected
+    /// before any is reported, unlike the streaming APIs.
+    pub reverse: bool,
+    /// Give each match a heuristic `score` between 0 and 1 for suggesting the likeliest intended
+    /// match, see `CapturesSer::score`. Not a measure of correctness.
+    pub score: bool,
+    /// Also report the content of each group lowercased as `normalized_content`, to group the
+    /// matches of a case-insensitive pattern by a common key while displaying the content as
+    /// found. It is taken from the content as matched, before `collapse_whitespace`, and is not
+    /// HTML-escaped.
+    pub normalize_case: bool,
+    /// Report for each match whether it starts inside a `"..."` or `'...'` string, as
+    /// `in_string`, and inside `(...)`, as `in_parens`, for code-aware search. A simple single
+    /// scan of the input: backslash escapes are not recognized, so `"a\"b"` ends at the second
+    /// quote, an apostrophe in prose opens a string, and parens inside strings are ignored.
+    pub quote_context: bool,
+    /// Report the grapheme cluster right after each match as `next_char`, or `null` at the end
+    /// of the input, to check where a match stops, e.g. that a number is not followed by more
+    /// digits.
+    pub next_char: bool,
+    /// Give each match a `length_rank` by its UTF-16 length, 1 for the longest, to highlight
+    /// the top matches without sorting them. Matches of equal length share a rank and the next
+    /// length down takes the next rank, so lengths 5, 3, 3 and 1 rank 1, 2, 2 and 3.
+    pub length_rank: bool,
+    /// The encoding of `start` and `end` in match results, `"utf8"` bytes by default. With
+    /// `"utf16"` or `"utf32"` they count UTF-16 code units or code points instead, while
+    /// `start_utf16` and `end_utf16` are kept for cross-reference unless `omit_utf16` is set.
+    pub offset_encoding: OffsetEncoding,
+}
+
+/// Units of the offsets of match results, see [`Options::offset_encoding`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OffsetEncoding {
+    /// UTF-8 bytes.
+    #[default]
+    Utf8,
+    /// UTF-16 code units, what JS strings index.
+    Utf16,
+    /// Code points, what `Array.from(string)` indexes.
+    Utf32,
+}
+
+impl Options {
+    pub fn from_js(options: JsValue) -> Result<Options, Error> {
+        if options.is_undefined() || options.is_null() {
+            return Ok(Options::default());
+        }
+        serde_wasm_bindgen::from_value(options).map_err(|e| Error::InvalidOptions {
+            message: e.to_string(),
+        })
+    }
+}
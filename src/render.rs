@@ -0,0 +1,384 @@
+use std::cmp::Reverse;
+use std::fmt::Write;
+
+use regex::bytes::Regex;
+use wasm_bindgen::prelude::*;
+
+use crate::find::captures_iter;
+use crate::flags::Flags;
+use crate::utf16::{ceil_char_boundary, floor_char_boundary, Utf16Cursor};
+use crate::{compile, Error};
+
+/// grep's default match color, bold red.
+const DEFAULT_COLOR: &str = "01;31";
+const RESET: &str = "\x1b[0m";
+
+pub(crate) fn colored(re: &Regex, text: &str, color: &str, flags: &Flags) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    let mut open = false;
+    for caps in captures_iter(re, text, 0, flags) {
+        let m = caps.get(0).unwrap();
+        // Matches without the u flag may split a char, widen them so the escapes don't.
+        let start = floor_char_boundary(text, m.start()).max(last);
+        let end = ceil_char_boundary(text, m.end());
+        if start >= end {
+            continue;
+        }
+        if !(open && start == last) {
+            if open {
+                out.push_str(RESET);
+            }
+            out.push_str(&text[last..start]);
+            out.push_str("\x1b[");
+            out.push_str(color);
+            out.push('m');
+        }
+        out.push_str(&text[start..end]);
+        last = end;
+        open = true;
+    }
+    if open {
+        out.push_str(RESET);
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+const DEFAULT_OPEN: &str = "\u{27E6}";
+const DEFAULT_CLOSE: &str = "\u{27E7}";
+
+/// Writes `text[*last..]` up to the end of the last of `spans` into `out`, with each span
+/// wrapped in the tags written by `open_tag` and `close_tag`. `spans` are `(start, end, label)`
+/// sorted by start and then by descending end, so that tags of nested spans are nested. A span
+/// that crosses the end of an open one is closed along with it.
+fn write_nested(
+    out: &mut String,
+    text: &str,
+    last: &mut usize,
+    spans: Vec<(usize, usize, String)>,
+    open_tag: impl Fn(&mut String, &str),
+    close_tag: impl Fn(&mut String, &str),
+) {
+    let mut copy_to = |out: &mut String, pos: usize| {
+        if pos > *last {
+            out.push_str(&text[*last..pos]);
+            *last = pos;
+        }
+    };
+    // The tags still open, innermost last. An empty span where an open one ends goes inside it.
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    for (start, end, label) in spans {
+        while let Some((top_end, _)) = stack.last() {
+            if *top_end > start || (*top_end == start && start == end) {
+                break;
+            }
+            let (top_end, top_label) = stack.pop().unwrap();
+            copy_to(out, top_end);
+            close_tag(out, &top_label);
+        }
+        copy_to(out, start);
+        open_tag(out, &label);
+        stack.push((end, label));
+    }
+    while let Some((end, label)) = stack.pop() {
+        copy_to(out, end);
+        close_tag(out, &label);
+    }
+}
+
+/// `text` with every match, and with `groups` every group, wrapped in numbered tags, see
+/// [`re_annotate`].
+pub(crate) fn annotate(
+    re: &Regex,
+    text: &str,
+    open: &str,
+    close: &str,
+    groups: bool,
+    flags: &Flags,
+) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (match_num, caps) in captures_iter(re, text, 0, flags).enumerate() {
+        let mut spans: Vec<_> = caps
+            .iter()
+            .enumerate()
+            .take(if groups { usize::MAX } else { 1 })
+            .filter_map(|(group_num, m)| {
+                let m = m?;
+                // Matches without the u flag may split a char, widen them so the tags don't.
+                let start = floor_char_boundary(text, m.start());
+                let end = ceil_char_boundary(text, m.end());
+                let label = match group_num {
+                    0 => match_num.to_string(),
+                    _ => format!("{}.{}", match_num, group_num),
+                };
+                Some((start, end, label))
+            })
+            .collect();
+        spans.sort_by_key(|&(start, end, _)| (start, Reverse(end)));
+        write_nested(
+            &mut out,
+            text,
+            &mut last,
+            spans,
+            |out, label| write!(out, "{}{}{}", open, label, close).unwrap(),
+            |out, label| write!(out, "{}/{}{}", open, label, close).unwrap(),
+        );
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+const DEFAULT_MARK_OPEN: &str = "{{";
+const DEFAULT_MARK_CLOSE: &str = "}}";
+
+/// The content of every match with its groups wrapped in markers, see [`re_find_marked`].
+pub(crate) fn marked(
+    re: &Regex,
+    text: &str,
+    open: &str,
+    close: &str,
+    flags: &Flags,
+) -> Vec<String> {
+    let names: Vec<_> = re.capture_names().collect();
+    captures_iter(re, text, 0, flags)
+        .map(|caps| {
+            let whole = caps.get(0).unwrap();
+            let start = floor_char_boundary(text, whole.start());
+            let end = ceil_char_boundary(text, whole.end());
+            let mut spans: Vec<_> = caps
+                .iter()
+                .enumerate()
+                .skip(1)
+                .filter_map(|(group_num, m)| {
+                    let m = m?;
+                    let label = match names[group_num] {
+                        Some(name) => name.to_string(),
+                        None => group_num.to_string(),
+                    };
+                    let start = floor_char_boundary(text, m.start());
+                    Some((start, ceil_char_boundary(text, m.end()), label))
+                })
+                .collect();
+            spans.sort_by_key(|&(start, end, _)| (start, Reverse(end)));
+            let mut out = String::with_capacity(end - start);
+            let mut last = start;
+            write_nested(
+                &mut out,
+                text,
+                &mut last,
+                spans,
+                |out, label| write!(out, "{}{}{}", open, label, close).unwrap(),
+                |out, _| write!(out, "{}/{}", open, close).unwrap(),
+            );
+            out.push_str(&text[last..end]);
+            out
+        })
+        .collect()
+}
+
+/// Returns `text` with every match wrapped in tags holding its match number, like
+/// `⟦0⟧matched⟦/0⟧`, to see the structure of the matches in plain text. `open` and `close`
+/// replace the `⟦` and `⟧` delimiters. With `groups`, every group that participated is tagged
+/// too, labeled `match_num.group_num`, and tags of nested groups are nested. Empty matches are
+/// still tagged, as `⟦0⟧⟦/0⟧`. Matches that split a char without the `u` flag are widened to
+/// the whole char.
+#[wasm_bindgen]
+pub fn re_annotate(
+    text: &str,
+    pattern: &str,
+    flags: &str,
+    open: Option<String>,
+    close: Option<String>,
+    groups: bool,
+) -> Result<String, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let open = open.as_deref().unwrap_or(DEFAULT_OPEN);
+    let close = close.as_deref().unwrap_or(DEFAULT_CLOSE);
+    Ok(annotate(&re, text, open, close, groups, &flags))
+}
+
+/// Returns the content of every match with the content of each group that participated
+/// wrapped in markers, like `{{1}}12{{/}}-{{2}}34{{/}}` for `(\d+)-(\d+)` on `12-34`, to show
+/// the structure of the captures as text for template extraction. Groups are labeled by name,
+/// or by number if unnamed, and markers of nested groups are nested. `open` and `close` replace
+/// the `{{` and `}}` delimiters. Groups that split a char without the `u` flag are widened to
+/// the whole char.
+#[wasm_bindgen]
+pub fn re_find_marked(
+    text: &str,
+    pattern: &str,
+    flags: &str,
+    open: Option<String>,
+    close: Option<String>,
+) -> Result<Vec<String>, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let open = open.as_deref().unwrap_or(DEFAULT_MARK_OPEN);
+    let close = close.as_deref().unwrap_or(DEFAULT_MARK_CLOSE);
+    Ok(marked(&re, text, open, close, &flags))
+}
+
+/// Returns `text` with every match wrapped in ANSI SGR escapes for printing to a terminal.
+/// `color` is the SGR parameter string, e.g. `"32"` or `"1;34"`, and defaults to grep's bold
+/// red. Touching matches share a single escape and empty matches are not highlighted.
+#[wasm_bindgen]
+pub fn re_find_colored(
+    text: &str,
+    pattern: &str,
+    flags: &str,
+    color: Option<String>,
+) -> Result<String, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let color = color.as_deref().unwrap_or(DEFAULT_COLOR);
+    Ok(colored(&re, text, color, &flags))
+}
+
+pub(crate) fn match_mask(re: &Regex, text: &str, flags: &Flags) -> Vec<u8> {
+    let mut mask = vec![0; text.encode_utf16().count()];
+    let mut cursor = Utf16Cursor::new(text);
+    for caps in captures_iter(re, text, 0, flags) {
+        let m = caps.get(0).unwrap();
+        let start = cursor.utf16(m.start());
+        let end = cursor.utf16(ceil_char_boundary(text, m.end()));
+        mask[start..end].fill(1);
+    }
+    mask
+}
+
+/// Returns one byte per UTF-16 code unit of `text`, not bit-packed, set to 1 where the unit is
+/// covered by a match and 0 elsewhere. A unit is covered if any byte of its char was matched.
+#[wasm_bindgen]
+pub fn re_match_mask(text: &str, pattern: &str, flags: &str) -> Result<Vec<u8>, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(match_mask(&re, text, &flags))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mask(text: &str, pattern: &str, flags: &str) -> Vec<u8> {
+        let flags = Flags::parse(flags);
+        match_mask(&compile(pattern, &flags).unwrap(), text, &flags)
+    }
+
+    #[test]
+    fn match_mask_covers_adjacent_matches() {
+        assert_eq!(mask("aab a", "a", "g"), [1, 1, 0, 0, 1]);
+        assert_eq!(mask("aab a", "a", ""), [1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn match_mask_covers_overlapping_groups_once() {
+        // Groups overlap the whole match, which is all the mask reports.
+        assert_eq!(mask("xaby", "(a(b))", "g"), [0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn match_mask_has_one_entry_per_utf16_unit() {
+        assert_eq!(mask("😀a😀", "😀", "gu"), [1, 1, 0, 1, 1]);
+        // A match of part of a char covers the whole char.
+        assert_eq!(mask("éa", r"(?-u:\xC3)", "g"), [1, 0]);
+    }
+
+    #[test]
+    fn marked_wraps_each_group() {
+        let flags = Flags::parse("g");
+        let re = compile(r"(\d+)-(\d+)", &flags).unwrap();
+        assert_eq!(
+            marked(&re, "a 1-22 b 3-4", "{{", "}}", &flags),
+            ["{{1}}1{{/}}-{{2}}22{{/}}", "{{1}}3{{/}}-{{2}}4{{/}}"]
+        );
+    }
+
+    #[test]
+    fn marked_nests_groups_and_uses_names() {
+        let flags = Flags::default();
+        let re = compile(r"(?P<date>(\d+)/(\d+))(x)?", &flags).unwrap();
+        assert_eq!(
+            marked(&re, "on 1/2.", "<", ">", &flags),
+            ["<date><2>1</>/<3>2</></>"]
+        );
+    }
+
+    fn color(text: &str, pattern: &str, color: &str, flags: &str) -> String {
+        let flags = Flags::parse(flags);
+        colored(&compile(pattern, &flags).unwrap(), text, color, &flags)
+    }
+
+    #[test]
+    fn colored_merges_touching_matches() {
+        assert_eq!(color("aab", "a", "01;31", "g"), "\x1b[01;31maa\x1b[0mb");
+        assert_eq!(
+            color("ab a", "a", "01;31", "g"),
+            "\x1b[01;31ma\x1b[0mb \x1b[01;31ma\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn colored_skips_empty_matches() {
+        assert_eq!(color("bab", "a*", "01;31", "g"), "b\x1b[01;31ma\x1b[0mb");
+        assert_eq!(color("bb", "", "01;31", "g"), "bb");
+    }
+
+    #[test]
+    fn colored_uses_the_given_color() {
+        assert_eq!(color("xay", "a", "32", ""), "x\x1b[32ma\x1b[0my");
+    }
+
+    #[test]
+    fn colored_widens_matches_that_split_a_char() {
+        assert_eq!(
+            color("éa", r"(?-u:\xC3)", "01;31", "g"),
+            "\x1b[01;31mé\x1b[0ma"
+        );
+        // Both bytes of `é` match separately but are colored once.
+        assert_eq!(
+            color("aé", r"(?-u:\xC3|\xA9)", "01;31", "g"),
+            "a\x1b[01;31mé\x1b[0m"
+        );
+    }
+
+    fn annotated(text: &str, pattern: &str, flags: &str, groups: bool) -> String {
+        let flags = Flags::parse(flags);
+        let re = compile(pattern, &flags).unwrap();
+        annotate(&re, text, DEFAULT_OPEN, DEFAULT_CLOSE, groups, &flags)
+    }
+
+    #[test]
+    fn annotate_tags_adjacent_and_empty_matches() {
+        assert_eq!(annotated("aab", "a", "g", false), "⟦0⟧a⟦/0⟧⟦1⟧a⟦/1⟧b");
+        assert_eq!(annotated("ab", "", "g", false), "⟦0⟧⟦/0⟧a⟦1⟧⟦/1⟧b⟦2⟧⟦/2⟧");
+        assert_eq!(annotated("ba", "a*", "", false), "⟦0⟧⟦/0⟧ba");
+    }
+
+    #[test]
+    fn annotate_nests_group_tags() {
+        assert_eq!(
+            annotated("xabc", "(a(b))c", "g", true),
+            "x⟦0⟧⟦0.1⟧a⟦0.2⟧b⟦/0.2⟧⟦/0.1⟧c⟦/0⟧"
+        );
+        // An empty group at the start of the match goes inside it, and groups that did not
+        // participate are left out.
+        assert_eq!(
+            annotated("a a", "(x?)a(y)?", "g", true),
+            "⟦0⟧⟦0.1⟧⟦/0.1⟧a⟦/0⟧ ⟦1⟧⟦1.1⟧⟦/1.1⟧a⟦/1⟧"
+        );
+        assert_eq!(annotated("ab", "(a)b", "", false), "⟦0⟧ab⟦/0⟧");
+    }
+
+    #[test]
+    fn annotate_uses_custom_delimiters() {
+        let flags = Flags::parse("g");
+        let re = compile(r"(\d)\d", &flags).unwrap();
+        assert_eq!(
+            annotate(&re, "12 é34", "<", ">", true, &flags),
+            "<0><0.1>1</0.1>2</0> é<1><1.1>3</1.1>4</1>"
+        );
+    }
+}
@@ -0,0 +1,906 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::str;
+
+use regex::bytes::Regex;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::error::{Field, FieldError};
+use crate::find::{captures_iter, content, find, MatchSer};
+use crate::flags::Flags;
+use crate::options::Options;
+use crate::utf16::{utf16_index_bytes, Utf16Cursor};
+use crate::{compile, compile_with, to_js, Error};
+
+/// Output bytes as a string, escaping bytes that are not valid UTF-8 like match content.
+pub(crate) fn into_string(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes).unwrap_or_else(|e| content(e.as_bytes()).into_owned())
+}
+
+pub(crate) fn replace(re: &Regex, text: &str, replacement: &str, flags: &Flags) -> String {
+    replace_counted(re, text, replacement, flags).0
+}
+
+/// [`replace`] along with the number of matches that were replaced.
+fn replace_counted(re: &Regex, text: &str, replacement: &str, flags: &Flags) -> (String, usize) {
+    let mut out = Vec::with_capacity(text.len());
+    let mut last = 0;
+    let mut count = 0;
+    for caps in captures_iter(re, text, 0, flags) {
+        let m = caps.get(0).unwrap();
+        out.extend_from_slice(&text.as_bytes()[last..m.start()]);
+        caps.expand(replacement.as_bytes(), &mut out);
+        last = m.end();
+        count += 1;
+    }
+    out.extend_from_slice(&text.as_bytes()[last..]);
+    (into_string(out), count)
+}
+
+/// A piece of a replacement template.
+enum Piece<'r> {
+    Literal(&'r str),
+    /// A group reference, by name or number as written, where `start..end` is the span of the
+    /// whole reference in the template.
+    Ref {
+        name: &'r str,
+        start: usize,
+        end: usize,
+    },
+}
+
+/// Splits `replacement` into literals and group references, following the rules of
+/// `regex::bytes::Captures::expand`: `$$` is a literal `$`, `${...}` takes everything up to the
+/// closing brace, and `$` is otherwise followed by the longest run of `[0-9A-Za-z_]`. A `$`
+/// that starts no reference is kept as is. References that parse as a number index groups.
+fn template(replacement: &str) -> Vec<Piece<'_>> {
+    let bytes = replacement.as_bytes();
+    let mut pieces = Vec::new();
+    let mut literal_start = 0;
+    let mut i = 0;
+    while let Some(dollar) = replacement[i..].find('$').map(|pos| i + pos) {
+        i = dollar + 1;
+        let (name, end) = match bytes.get(i) {
+            Some(b'$') => {
+                pieces.push(Piece::Literal(&replacement[literal_start..i]));
+                i += 1;
+                literal_start = i;
+                continue;
+            }
+            Some(b'{') => match replacement[i..].find('}').map(|pos| i + pos) {
+                Some(close) => (&replacement[i + 1..close], close + 1),
+                None => continue,
+            },
+            _ => {
+                let len = bytes[i..]
+                    .iter()
+                    .take_while(|&&b| b == b'_' || b.is_ascii_alphanumeric())
+                    .count();
+                if len == 0 {
+                    continue;
+                }
+                (&replacement[i..i + len], i + len)
+            }
+        };
+        pieces.push(Piece::Literal(&replacement[literal_start..dollar]));
+        pieces.push(Piece::Ref {
+            name,
+            start: dollar,
+            end,
+        });
+        i = end;
+        literal_start = end;
+    }
+    pieces.push(Piece::Literal(&replacement[literal_start..]));
+    pieces
+}
+
+/// Checks that every reference of `replacement` names a group of `re`. `expand` silently
+/// replaces unknown references with nothing, which is rarely what was meant.
+pub(crate) fn validate_replacement(re: &Regex, replacement: &str) -> Result<(), Error> {
+    for piece in template(replacement) {
+        let Piece::Ref { name, start, end } = piece else {
+            continue;
+        };
+        let known = match name.parse::<usize>() {
+            Ok(group) => group < re.captures_len(),
+            Err(_) => re.capture_names().any(|n| n == Some(name)),
+        };
+        if !known {
+            return Err(Error::InvalidReplacementReference {
+                reference: replacement[start..end].to_string(),
+                start,
+                end,
+                start_utf16: utf16_index_bytes(replacement, start),
+                end_utf16: utf16_index_bytes(replacement, end),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// [`replace`], except that a reference to a group that did not participate in a match expands
+/// to its entry in `defaults`, keyed by the name or number as written, instead of nothing.
+pub(crate) fn replace_with_defaults(
+    re: &Regex,
+    text: &str,
+    replacement: &str,
+    defaults: &HashMap<String, String>,
+    flags: &Flags,
+) -> String {
+    let pieces = template(replacement);
+    let mut out = Vec::with_capacity(text.len());
+    let mut last = 0;
+    for caps in captures_iter(re, text, 0, flags) {
+        let m = caps.get(0).unwrap();
+        out.extend_from_slice(&text.as_bytes()[last..m.start()]);
+        for piece in &pieces {
+            match *piece {
+                Piece::Literal(literal) => out.extend_from_slice(literal.as_bytes()),
+                Piece::Ref { name, .. } => {
+                    let group = match name.parse::<usize>() {
+                        Ok(group) => caps.get(group),
+                        Err(_) => caps.name(name),
+                    };
+                    match (group, defaults.get(name)) {
+                        (Some(group), _) => out.extend_from_slice(group.as_bytes()),
+                        (None, Some(default)) => out.extend_from_slice(default.as_bytes()),
+                        (None, None) => {}
+                    }
+                }
+            }
+        }
+        last = m.end();
+    }
+    out.extend_from_slice(&text.as_bytes()[last..]);
+    into_string(out)
+}
+
+/// Replaces each match by the entry of `dict` for its content, or by nothing with
+/// `remove_missing` when there is none. Otherwise matches without an entry are kept.
+pub(crate) fn replace_dict(
+    re: &Regex,
+    text: &str,
+    dict: &HashMap<String, String>,
+    remove_missing: bool,
+    flags: &Flags,
+) -> String {
+    let mut out = Vec::with_capacity(text.len());
+    let mut last = 0;
+    for caps in captures_iter(re, text, 0, flags) {
+        let m = caps.get(0).unwrap();
+        out.extend_from_slice(&text.as_bytes()[last..m.start()]);
+        match dict.get(content(m.as_bytes()).as_ref()) {
+            Some(value) => out.extend_from_slice(value.as_bytes()),
+            None if remove_missing => {}
+            None => out.extend_from_slice(m.as_bytes()),
+        }
+        last = m.end();
+    }
+    out.extend_from_slice(&text.as_bytes()[last..]);
+    into_string(out)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentKind {
+    /// Input between matches, copied to the result.
+    Kept,
+    /// The expanded replacement of a match.
+    Inserted,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SegmentSer {
+    pub text: String,
+    pub kind: SegmentKind,
+}
+
+/// The result of [`replace`] as the pieces it is built from, leaving out empty ones.
+pub(crate) fn replace_segments(
+    re: &Regex,
+    text: &str,
+    replacement: &str,
+    flags: &Flags,
+) -> Vec<SegmentSer> {
+    let mut segments = Vec::new();
+    let mut push = |bytes: Vec<u8>, kind| {
+        if !bytes.is_empty() {
+            segments.push(SegmentSer {
+                text: into_string(bytes),
+                kind,
+            });
+        }
+    };
+    let mut last = 0;
+    for caps in captures_iter(re, text, 0, flags) {
+        let m = caps.get(0).unwrap();
+        push(text.as_bytes()[last..m.start()].to_vec(), SegmentKind::Kept);
+        let mut inserted = Vec::new();
+        caps.expand(replacement.as_bytes(), &mut inserted);
+        push(inserted, SegmentKind::Inserted);
+        last = m.end();
+    }
+    push(text.as_bytes()[last..].to_vec(), SegmentKind::Kept);
+    segments
+}
+
+/// `text` split into records at every match, trimmed and without empty ones, joined with
+/// `separator`. With `keep_delimiters` each record keeps the trimmed match that ends it.
+pub(crate) fn reflow(
+    re: &Regex,
+    text: &str,
+    separator: &str,
+    keep_delimiters: bool,
+    flags: &Flags,
+) -> String {
+    let flags = Flags {
+        global: true,
+        ..*flags
+    };
+    let bytes = text.as_bytes();
+    let mut records = Vec::new();
+    let mut last = 0;
+    for caps in captures_iter(re, text, 0, &flags) {
+        let m = caps.get(0).unwrap();
+        let mut record = bytes[last..m.start()].trim_ascii().to_vec();
+        if keep_delimiters {
+            record.extend_from_slice(m.as_bytes().trim_ascii());
+        }
+        records.push(record);
+        last = m.end();
+    }
+    records.push(bytes[last..].trim_ascii().to_vec());
+    records.retain(|record| !record.is_empty());
+    into_string(records.join(separator.as_bytes()))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplacedSer {
+    pub result: String,
+    /// How many matches were replaced.
+    pub replacement_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FoundAndReplacedSer<'a> {
+    #[serde(flatten)]
+    pub matches: MatchSer<'a>,
+    #[serde(flatten)]
+    pub replaced: ReplacedSer,
+}
+
+pub(crate) fn find_and_replace<'a>(
+    re: &'a Regex,
+    text: &'a str,
+    replacement: &str,
+    flags: &Flags,
+) -> FoundAndReplacedSer<'a> {
+    let (result, replacement_count) = replace_counted(re, text, replacement, flags);
+    FoundAndReplacedSer {
+        matches: find(re, text, flags),
+        replaced: ReplacedSer {
+            result,
+            replacement_count,
+        },
+    }
+}
+
+/// The expanded replacement of each match, concatenated without the text around them.
+pub(crate) fn replace_only_matches(
+    re: &Regex,
+    text: &str,
+    replacement: &str,
+    flags: &Flags,
+) -> String {
+    let mut out = Vec::new();
+    for caps in captures_iter(re, text, 0, flags) {
+        caps.expand(replacement.as_bytes(), &mut out);
+    }
+    into_string(out)
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplacedSpanSer {
+    pub match_num: usize,
+    /// The span of the match in the original text.
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+    pub replacement: String,
+    /// The UTF-16 length of `replacement` minus that of the match it replaces.
+    pub delta_utf16: isize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplacePreviewSer {
+    pub result: String,
+    pub spans: Vec<ReplacedSpanSer>,
+}
+
+/// [`replace`] along with what each match was replaced by.
+pub(crate) fn replace_preview(
+    re: &Regex,
+    text: &str,
+    replacement: &str,
+    flags: &Flags,
+) -> ReplacePreviewSer {
+    let mut cursor = Utf16Cursor::new(text);
+    let mut expanded = Vec::new();
+    let spans = captures_iter(re, text, 0, flags)
+        .enumerate()
+        .map(|(match_num, caps)| {
+            let m = caps.get(0).unwrap();
+            expanded.clear();
+            caps.expand(replacement.as_bytes(), &mut expanded);
+            let replacement = content(&expanded).into_owned();
+            let start_utf16 = cursor.utf16(m.start());
+            let end_utf16 = cursor.utf16(m.end());
+            let delta_utf16 =
+                replacement.encode_utf16().count() as isize - (end_utf16 - start_utf16) as isize;
+            ReplacedSpanSer {
+                match_num,
+                start_utf16,
+                end_utf16,
+                replacement,
+                delta_utf16,
+            }
+        })
+        .collect();
+    ReplacePreviewSer {
+        result: replace(re, text, replacement, flags),
+        spans,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RemovedSer<'a> {
+    /// Where the match started in the original text.
+    pub start_utf16: usize,
+    pub content: Cow<'a, str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplacedWithRemovedSer<'a> {
+    pub result: String,
+    pub removed: Vec<RemovedSer<'a>>,
+}
+
+/// [`replace`] along with the content each match had before it was replaced.
+pub(crate) fn replace_with_removed<'a>(
+    re: &Regex,
+    text: &'a str,
+    replacement: &str,
+    flags: &Flags,
+) -> ReplacedWithRemovedSer<'a> {
+    let mut cursor = Utf16Cursor::new(text);
+    let mut out = Vec::with_capacity(text.len());
+    let mut last = 0;
+    let mut removed = Vec::new();
+    for caps in captures_iter(re, text, 0, flags) {
+        let m = caps.get(0).unwrap();
+        out.extend_from_slice(&text.as_bytes()[last..m.start()]);
+        caps.expand(replacement.as_bytes(), &mut out);
+        last = m.end();
+        removed.push(RemovedSer {
+            start_utf16: cursor.utf16(m.start()),
+            content: content(&text.as_bytes()[m.range()]),
+        });
+    }
+    out.extend_from_slice(&text.as_bytes()[last..]);
+    ReplacedWithRemovedSer {
+        result: into_string(out),
+        removed,
+    }
+}
+
+/// The UTF-16 length of [`replace`]'s result, without building it.
+pub(crate) fn replace_result_len(
+    re: &Regex,
+    text: &str,
+    replacement: &str,
+    flags: &Flags,
+) -> usize {
+    let mut len = 0;
+    let mut last = 0;
+    let mut expanded = Vec::new();
+    for caps in captures_iter(re, text, 0, flags) {
+        let m = caps.get(0).unwrap();
+        expanded.clear();
+        caps.expand(replacement.as_bytes(), &mut expanded);
+        let kept = text.get(last..m.start());
+        let (Some(kept), Ok(inserted)) = (kept, str::from_utf8(&expanded)) else {
+            // Pieces that split a char may join up in the output, measure the real thing.
+            return replace(re, text, replacement, flags).encode_utf16().count();
+        };
+        len += kept.encode_utf16().count() + inserted.encode_utf16().count();
+        last = m.end();
+    }
+    match text.get(last..) {
+        Some(rest) => len + rest.encode_utf16().count(),
+        None => replace(re, text, replacement, flags).encode_utf16().count(),
+    }
+}
+
+/// Replaces the first match of `pattern` in `text`, or every match with the `g` flag, with
+/// `replacement`. `$1`, `${name}` and `$$` are expanded as documented for
+/// `regex::Captures::expand`.
+#[wasm_bindgen]
+pub fn re_replace(
+    text: &str,
+    pattern: &str,
+    replacement: &str,
+    flags: &str,
+) -> Result<String, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(replace(&re, text, replacement, &flags))
+}
+
+/// Returns `{ matches, result, replacement_count }`: the matches as `re_find` reports them along
+/// with the output of `re_replace` and the number of matches replaced, compiling the pattern once
+/// for views that show both.
+#[wasm_bindgen]
+pub fn re_find_and_replace(
+    text: &str,
+    pattern: &str,
+    replacement: &str,
+    flags: &str,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&find_and_replace(&re, text, replacement, &flags)))
+}
+
+/// Like [`re_replace`], configured by an [`Options`] object. With `only_matches` only the
+/// expanded replacements are returned, so `(\d+)` replaced by `$1,` turns `a1b22c` into `1,22,`
+/// with the `g` flag.
+#[wasm_bindgen]
+pub fn re_replace_with_options(
+    text: &str,
+    pattern: &str,
+    replacement: &str,
+    flags: &str,
+    options: JsValue,
+) -> Result<String, Error> {
+    let flags = Flags::parse(flags);
+    let options = Options::from_js(options)?;
+    let re = compile_with(pattern, &flags, &options)?;
+    Ok(if options.only_matches {
+        replace_only_matches(&re, text, replacement, &flags)
+    } else {
+        replace(&re, text, replacement, &flags)
+    })
+}
+
+/// Compiles `pattern` and checks `replacement` against it before replacing, tagging each error
+/// with the input it came from. See [`re_replace_validated`].
+pub(crate) fn replace_validated(
+    text: &str,
+    pattern: &str,
+    replacement: &str,
+    flags: &Flags,
+) -> Result<ReplacedSer, FieldError> {
+    let re = compile(pattern, flags).map_err(|error| FieldError {
+        field: Field::Pattern,
+        error,
+    })?;
+    validate_replacement(&re, replacement).map_err(|error| FieldError {
+        field: Field::Replacement,
+        error,
+    })?;
+    let (result, replacement_count) = replace_counted(&re, text, replacement, flags);
+    Ok(ReplacedSer {
+        result,
+        replacement_count,
+    })
+}
+
+/// Like [`re_replace`], but first checks that every `$` reference of `replacement` names a group
+/// of `pattern`, and returns `{ result, replacement_count }` with the number of replaced matches.
+/// Errors are the usual error objects with an added `field` of `"pattern"` (syntax errors, or
+/// a pattern too big to compile) or `"replacement"` (an `invalid_replacement_reference`), so
+/// that a form can validate both inputs and preview the result in one call.
+#[wasm_bindgen]
+pub fn re_replace_validated(
+    text: &str,
+    pattern: &str,
+    replacement: &str,
+    flags: &str,
+) -> Result<JsValue, FieldError> {
+    let flags = Flags::parse(flags);
+    Ok(to_js(&replace_validated(
+        text,
+        pattern,
+        replacement,
+        &flags,
+    )?))
+}
+
+/// Like [`re_replace`], but returns `{ result, spans }` where `spans` has one
+/// `{ match_num, start_utf16, end_utf16, replacement, delta_utf16 }` per replaced match. The span
+/// is that of the match in `text`, and `delta_utf16` is how much longer the replacement is than
+/// the match, negative if it is shorter, so that offsets after it can be shifted without
+/// measuring the result. Bytes that are not valid UTF-8 are measured in their `\x..` escaped
+/// form.
+#[wasm_bindgen]
+pub fn re_replace_preview(
+    text: &str,
+    pattern: &str,
+    replacement: &str,
+    flags: &str,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&replace_preview(&re, text, replacement, &flags)))
+}
+
+/// Like [`re_replace`], but returns `{ result, removed }` where `removed` has one
+/// `{ start_utf16, content }` per replaced match, with the match's offset and content in `text`,
+/// for showing what a replacement took out. Without the `g` flag only the first match is
+/// replaced and listed.
+#[wasm_bindgen]
+pub fn re_replace_with_removed(
+    text: &str,
+    pattern: &str,
+    replacement: &str,
+    flags: &str,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&replace_with_removed(&re, text, replacement, &flags)))
+}
+
+/// Like [`re_replace`], but `defaults` maps group names, or numbers such as `"1"`, to what their
+/// references in `replacement` expand to in matches they did not participate in, instead of
+/// the empty string. `defaults` can be a plain object or a `Map` of strings.
+#[wasm_bindgen]
+pub fn re_replace_with_defaults(
+    text: &str,
+    pattern: &str,
+    replacement: &str,
+    defaults: JsValue,
+    flags: &str,
+) -> Result<String, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let defaults: HashMap<String, String> =
+        serde_wasm_bindgen::from_value(defaults).map_err(|e| Error::InvalidOptions {
+            message: e.to_string(),
+        })?;
+    Ok(replace_with_defaults(
+        &re,
+        text,
+        replacement,
+        &defaults,
+        &flags,
+    ))
+}
+
+/// Returns the result of [`re_replace`] as `{ text, kind }` segments in order, where `kind` is
+/// `"kept"` for input between matches and `"inserted"` for the expanded replacement of a match,
+/// so a preview can color what was inserted. Joining the `text` of every segment gives the
+/// result. Empty segments, from touching matches or empty replacements, are left out, so
+/// removed matches leave no trace and two segments of the same kind can follow each other.
+#[wasm_bindgen]
+pub fn re_replace_segments(
+    text: &str,
+    pattern: &str,
+    replacement: &str,
+    flags: &str,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&replace_segments(&re, text, replacement, &flags)))
+}
+
+/// Reformats `text` treating every match of `pattern` as the end of a record, whether or not
+/// `flags` contains `g`: records are trimmed of ASCII whitespace, empty ones are dropped, and the
+/// rest are put one per line, or with `join` on a single line separated by spaces. The matches
+/// themselves are dropped unless `keep_delimiters` is set, which keeps each one, trimmed, at the
+/// end of the record before it. So `,` turns `a, b,c` into `a\nb\nc`, and `\n` with `join`
+/// turns that back into `a b c`.
+#[wasm_bindgen]
+pub fn re_reflow(
+    text: &str,
+    pattern: &str,
+    flags: &str,
+    join: bool,
+    keep_delimiters: bool,
+) -> Result<String, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let separator = if join { " " } else { "\n" };
+    Ok(reflow(&re, text, separator, keep_delimiters, &flags))
+}
+
+/// Replaces each match of `pattern` by the value `dict` maps its content to, such as color
+/// names to hex codes, taking the replacements from data instead of a template. `dict` can be a
+/// plain object or a `Map` of strings, and its values are inserted as is, without expanding `$`.
+/// Matches `dict` has no entry for are kept, or removed with `remove_missing`. Matched bytes that
+/// are not valid UTF-8 are looked up with `\x..` escapes, as match content is reported.
+#[wasm_bindgen]
+pub fn re_replace_dict(
+    text: &str,
+    pattern: &str,
+    dict: JsValue,
+    remove_missing: bool,
+    flags: &str,
+) -> Result<String, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let dict: HashMap<String, String> =
+        serde_wasm_bindgen::from_value(dict).map_err(|e| Error::InvalidOptions {
+            message: e.to_string(),
+        })?;
+    Ok(replace_dict(&re, text, &dict, remove_missing, &flags))
+}
+
+/// Returns the UTF-16 length of what [`re_replace`] would return, without building the
+/// replaced string.
+#[wasm_bindgen]
+pub fn re_replace_result_len(
+    text: &str,
+    pattern: &str,
+    replacement: &str,
+    flags: &str,
+) -> Result<usize, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(replace_result_len(&re, text, replacement, &flags))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_result_len_matches_the_real_result() {
+        let flags = Flags::parse("g");
+        let re = compile(r"(?P<word>\w+)@(\d)", &flags).unwrap();
+        let text = "a@1 😀 bc@2";
+        let replacement = "<${word}:$2$$>";
+        let result = replace(&re, text, replacement, &flags);
+        assert_eq!(result, "<a:1$> 😀 <bc:2$>");
+        let len = replace_result_len(&re, text, replacement, &flags);
+        assert_eq!(len, result.encode_utf16().count());
+    }
+
+    #[test]
+    fn replace_result_len_measures_split_chars() {
+        // Each byte of `é` is replaced by itself, so the pieces join back into one char.
+        let flags = Flags::parse("g");
+        let re = compile(r"(.)", &flags).unwrap();
+        assert_eq!(replace_result_len(&re, "é", "$1", &flags), 1);
+    }
+
+    #[test]
+    fn replace_only_matches_drops_the_text_between() {
+        let flags = Flags::parse("g");
+        let re = compile(r"(\d+)", &flags).unwrap();
+        assert_eq!(replace_only_matches(&re, "a1b22c", "$1,", &flags), "1,22,");
+        let flags = Flags::default();
+        assert_eq!(replace_only_matches(&re, "a1b22c", "$1,", &flags), "1,");
+    }
+
+    #[test]
+    fn replace_validated_counts_replacements() {
+        let flags = Flags::parse("g");
+        let replaced = replace_validated("1 22 x", r"(?P<n>\d+)", "<${n}$$>", &flags).unwrap();
+        assert_eq!(replaced.result, "<1$> <22$> x");
+        assert_eq!(replaced.replacement_count, 2);
+    }
+
+    #[test]
+    fn replace_validated_blames_the_pattern() {
+        let flags = Flags::default();
+        let err = replace_validated("", "(a", "$1", &flags).unwrap_err();
+        assert_eq!(err.field, Field::Pattern);
+        assert!(matches!(err.error, Error::Syntax(_)));
+
+        let err = replace_validated("", r"\w{1000}{1000}", "", &Flags::parse("u")).unwrap_err();
+        assert_eq!(err.field, Field::Pattern);
+        assert!(matches!(err.error, Error::CompiledTooBig { .. }));
+    }
+
+    #[test]
+    fn replace_validated_blames_the_replacement() {
+        let flags = Flags::default();
+        let err = replace_validated("ab", "(?P<x>a)", "é${y}", &flags).unwrap_err();
+        assert_eq!(err.field, Field::Replacement);
+        match err.error {
+            Error::InvalidReplacementReference {
+                reference,
+                start,
+                end,
+                start_utf16,
+                end_utf16,
+            } => {
+                assert_eq!(reference, "${y}");
+                assert_eq!((start, end, start_utf16, end_utf16), (2, 6, 1, 5));
+            }
+            other => panic!("unexpected error {other:?}"),
+        }
+        // `$2` is past the last group, but `$1`, `$x` and `$$2` are fine.
+        assert!(replace_validated("ab", "(?P<x>a)", "$1$x$$2", &flags).is_ok());
+        let err = replace_validated("ab", "(?P<x>a)", "$2", &flags).unwrap_err();
+        assert_eq!(err.field, Field::Replacement);
+    }
+
+    #[test]
+    fn replace_preview_deltas_shift_later_offsets() {
+        let flags = Flags::parse("g");
+        let re = compile(r"\d+|😀", &flags).unwrap();
+        let text = "a1 😀 333";
+        let preview = replace_preview(&re, text, "<$0>", &flags);
+        // Wrapping adds two units to every match, whatever its length.
+        let deltas: Vec<_> = preview.spans.iter().map(|s| s.delta_utf16).collect();
+        assert_eq!(deltas, [2, 2, 2]);
+
+        let preview = replace_preview(&re, text, "é", &flags);
+        assert_eq!(preview.result, "aé é é");
+        let spans: Vec<_> = preview
+            .spans
+            .iter()
+            .map(|s| (s.start_utf16, s.end_utf16, s.delta_utf16))
+            .collect();
+        assert_eq!(spans, [(1, 2, 0), (3, 5, -1), (6, 9, -2)]);
+        // The deltas add up to the change in length of the whole text.
+        let total: isize = spans.iter().map(|s| s.2).sum();
+        let grown = preview.result.encode_utf16().count() as isize;
+        assert_eq!(grown - text.encode_utf16().count() as isize, total);
+    }
+
+    #[test]
+    fn replace_with_defaults_fills_in_missing_groups() {
+        let flags = Flags::parse("g");
+        let re = compile(r"(?P<x>a)?b(c)?", &flags).unwrap();
+        let defaults = HashMap::from([
+            ("x".to_string(), "-".to_string()),
+            ("2".to_string(), "?".to_string()),
+        ]);
+        // `${x}` and `$2` take their default only where the group didn't match.
+        let replaced = replace_with_defaults(&re, "ab b bc", "[${x}$2]", &defaults, &flags);
+        assert_eq!(replaced, "[a?] [-?] [-c]");
+        // Groups without a default still expand to nothing, and `$$` stays a literal `$`.
+        let replaced = replace_with_defaults(&re, "b", "$$$1${x}", &HashMap::new(), &flags);
+        assert_eq!(replaced, "$");
+    }
+
+    #[test]
+    fn find_and_replace_outputs_agree() {
+        let text = "ab é😀 cd";
+        for flags in ["g", ""] {
+            let flags = Flags::parse(flags);
+            let re = compile(r"(\S)\S*", &flags).unwrap();
+            let both = find_and_replace(&re, text, "[$1]", &flags);
+            assert_eq!(both.replaced.replacement_count, both.matches.matches.len());
+            // Splicing `[first char]` over each reported match rebuilds the result.
+            let mut rebuilt = String::new();
+            let mut last = 0;
+            for m in &both.matches.matches {
+                rebuilt += &text[last..m.groups[0].start];
+                rebuilt += &format!("[{}]", m.groups[1].content);
+                last = m.groups[0].end;
+            }
+            rebuilt += &text[last..];
+            assert_eq!(both.replaced.result, rebuilt);
+        }
+    }
+
+    #[test]
+    fn find_and_replace_serializes_the_requested_keys() {
+        let flags = Flags::parse("g");
+        let re = compile("a", &flags).unwrap();
+        let json = serde_json::to_value(find_and_replace(&re, "aba", "x", &flags)).unwrap();
+        let keys: Vec<_> = json.as_object().unwrap().keys().cloned().collect();
+        assert_eq!(keys, ["matches", "replacement_count", "result"]);
+        assert_eq!(json["replacement_count"], 2);
+    }
+
+    #[test]
+    fn replace_dict_looks_up_whole_matches() {
+        let flags = Flags::parse("g");
+        let re = compile(r"\w+", &flags).unwrap();
+        let dict = HashMap::from([
+            ("red".to_string(), "#f00".to_string()),
+            ("blue".to_string(), "$0".to_string()),
+        ]);
+        let text = "red, green and blue";
+        assert_eq!(
+            replace_dict(&re, text, &dict, false, &flags),
+            "#f00, green and $0"
+        );
+        assert_eq!(replace_dict(&re, text, &dict, true, &flags), "#f00,   $0");
+    }
+
+    #[test]
+    fn replace_dict_keys_invalid_utf8_by_escape() {
+        let flags = Flags::parse("g");
+        let re = compile(r"(?-u:\xC3)", &flags).unwrap();
+        let dict = HashMap::from([(r"\xC3".to_string(), "A".to_string())]);
+        // The lead byte of `é` is replaced, and the stray byte left is escaped in turn.
+        assert_eq!(replace_dict(&re, "é", &dict, false, &flags), r"A\xA9");
+    }
+
+    fn segments(pattern: &str, text: &str, replacement: &str) -> Vec<(String, SegmentKind)> {
+        let flags = Flags::parse("g");
+        let re = compile(pattern, &flags).unwrap();
+        let segments = replace_segments(&re, text, replacement, &flags);
+        let joined: String = segments.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(joined, replace(&re, text, replacement, &flags));
+        segments.into_iter().map(|s| (s.text, s.kind)).collect()
+    }
+
+    #[test]
+    fn replace_segments_mark_inserted_text() {
+        use SegmentKind::{Inserted, Kept};
+        let seg = |text: &str, kind| (text.to_string(), kind);
+        assert_eq!(
+            segments(r"\d", "12a3", "$0,"),
+            [
+                seg("1,", Inserted),
+                seg("2,", Inserted),
+                seg("a", Kept),
+                seg("3,", Inserted)
+            ]
+        );
+        // Empty matches insert without leaving empty kept segments between them.
+        assert_eq!(
+            segments("", "ab", "|"),
+            [
+                seg("|", Inserted),
+                seg("a", Kept),
+                seg("|", Inserted),
+                seg("b", Kept),
+                seg("|", Inserted)
+            ]
+        );
+        // Removed matches leave no segment, so kept text can follow kept text.
+        assert_eq!(segments(r"\d", "a1b", ""), [seg("a", Kept), seg("b", Kept)]);
+    }
+
+    #[test]
+    fn reflow_splits_and_rejoins_records() {
+        let flags = Flags::default();
+        let comma = compile(",", &flags).unwrap();
+        let lines = reflow(&comma, " a, b,,c ,", "\n", false, &flags);
+        assert_eq!(lines, "a\nb\nc");
+        let newline = compile("\n", &flags).unwrap();
+        assert_eq!(reflow(&newline, &lines, " ", false, &flags), "a b c");
+    }
+
+    #[test]
+    fn reflow_can_keep_delimiters() {
+        let flags = Flags::default();
+        let end = compile(r"[.;]\s*", &flags).unwrap();
+        assert_eq!(
+            reflow(&end, "One.  Two;three", "\n", true, &flags),
+            "One.\nTwo;\nthree"
+        );
+    }
+
+    #[test]
+    fn replace_with_removed_lists_each_replaced_match() {
+        let flags = Flags::parse("g");
+        let re = compile(r"\d+", &flags).unwrap();
+        let replaced = replace_with_removed(&re, "é12 x 345", "#", &flags);
+        assert_eq!(replaced.result, "é# x #");
+        let removed: Vec<_> = replaced
+            .removed
+            .iter()
+            .map(|r| (r.start_utf16, &*r.content))
+            .collect();
+        assert_eq!(removed, [(1, "12"), (6, "345")]);
+    }
+
+    #[test]
+    fn replace_with_removed_without_g_lists_the_first_match() {
+        let flags = Flags::default();
+        let re = compile(r"(\d)\d*", &flags).unwrap();
+        let replaced = replace_with_removed(&re, "a12 b34", "<$1>", &flags);
+        assert_eq!(replaced.result, "a<1> b34");
+        assert_eq!(replaced.removed.len(), 1);
+        assert_eq!(replaced.removed[0].content, "12");
+    }
+}
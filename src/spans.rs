@@ -0,0 +1,102 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{to_js, Error};
+
+/// Whether the half-open spans `a_start..a_end` and `b_start..b_end` share a position. Spans that
+/// only touch, like `0..2` and `2..4`, don't overlap. An empty span overlaps the spans it is
+/// strictly inside, but not one it sits at the edge of, nor another empty span.
+#[wasm_bindgen]
+pub fn spans_overlap(a_start: usize, a_end: usize, b_start: usize, b_end: usize) -> bool {
+    a_start < b_end && b_start < a_end
+}
+
+/// Every pair of indices `(i, j)`, `i < j`, of `spans` that overlap as defined by
+/// [`spans_overlap`], sorted.
+pub(crate) fn overlaps(spans: &[(usize, usize)]) -> Result<Vec<(usize, usize)>, Error> {
+    if let Some(&(start, end)) = spans.iter().find(|(start, end)| start > end) {
+        return Err(Error::InvalidRange { start, end });
+    }
+    let mut order: Vec<_> = (0..spans.len()).collect();
+    order.sort_by_key(|&i| spans[i].0);
+    // The spans started so far that extend past the current start.
+    let mut active: Vec<usize> = Vec::new();
+    let mut pairs = Vec::new();
+    for j in order {
+        let (start, end) = spans[j];
+        active.retain(|&i| spans[i].1 > start);
+        for &i in &active {
+            if spans[i].0 < end {
+                pairs.push((i.min(j), i.max(j)));
+            }
+        }
+        active.push(j);
+    }
+    pairs.sort_unstable();
+    Ok(pairs)
+}
+
+/// Returns every pair `[i, j]`, `i < j`, of `spans` given as `[[start, end], ...]` that overlap
+/// as [`spans_overlap`] defines it, sorted, for finding conflicting highlights. Found with a sweep
+/// over the spans by start.
+#[wasm_bindgen]
+pub fn overlapping_pairs(spans: JsValue) -> Result<JsValue, Error> {
+    let spans: Vec<(usize, usize)> =
+        serde_wasm_bindgen::from_value(spans).map_err(|e| Error::InvalidOptions {
+            message: e.to_string(),
+        })?;
+    Ok(to_js(&overlaps(&spans)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn touching_spans_do_not_overlap() {
+        assert!(!spans_overlap(0, 2, 2, 4));
+        assert!(!spans_overlap(2, 4, 0, 2));
+        assert!(!spans_overlap(2, 2, 0, 2));
+        assert!(!spans_overlap(1, 1, 1, 1));
+    }
+
+    #[test]
+    fn nested_spans_overlap() {
+        assert!(spans_overlap(0, 5, 1, 3));
+        assert!(spans_overlap(1, 3, 0, 5));
+        assert!(spans_overlap(0, 5, 2, 2));
+        assert_eq!(
+            overlaps(&[(0, 5), (5, 6), (1, 3), (2, 2), (0, 0)]).unwrap(),
+            [(0, 2), (0, 3), (2, 3)]
+        );
+    }
+
+    #[test]
+    fn overlaps_rejects_reversed_spans() {
+        assert!(matches!(
+            overlaps(&[(0, 1), (3, 2)]),
+            Err(Error::InvalidRange { start: 3, end: 2 })
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn overlaps_finds_every_overlapping_pair(
+            spans in prop::collection::vec((0usize..10, 0usize..4), 0..12),
+        ) {
+            let spans: Vec<_> =
+                spans.into_iter().map(|(start, len)| (start, start + len)).collect();
+            let mut expected = Vec::new();
+            for i in 0..spans.len() {
+                for j in i + 1..spans.len() {
+                    let ((a_start, a_end), (b_start, b_end)) = (spans[i], spans[j]);
+                    if spans_overlap(a_start, a_end, b_start, b_end) {
+                        expected.push((i, j));
+                    }
+                }
+            }
+            prop_assert_eq!(overlaps(&spans).unwrap(), expected);
+        }
+    }
+}
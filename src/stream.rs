@@ -0,0 +1,376 @@
+use std::mem;
+use std::str;
+
+use regex::bytes::Regex;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::find::{captures_iter, collect_resumed, CapturesIter, MatchSer};
+use crate::flags::Flags;
+use crate::replace::into_string;
+use crate::utf16::{floor_char_boundary, Utf16Cursor};
+use crate::{compile, to_js, Error};
+
+/// How far past the longest possible match assertions such as `\b` or `$` may look, one char.
+const LOOKAHEAD_MARGIN: usize = 4;
+
+/// Replaces the matches of a pattern in input fed in chunks, such as a file read piece by
+/// piece, with the same result as [`crate::replace::re_replace`] over the whole input.
+///
+/// Only the input that later chunks could still change the matches in is held back: the
+/// longest match the pattern allows plus one char. Patterns without a maximum match length,
+/// like `a+`, can't bound this, so their input is held until [`StreamingReplacer::finish`].
+#[wasm_bindgen]
+pub struct StreamingReplacer {
+    re: Regex,
+    flags: Flags,
+    replacement: String,
+    max_len: Option<usize>,
+    /// The input not emitted yet, after the char before it for anchors to see.
+    buffer: String,
+    /// Where the input not emitted yet starts in `buffer`.
+    pending: usize,
+    /// Whether the last match ended at `pending`, so an empty match there is skipped as in a
+    /// single scan.
+    after_match: bool,
+    /// Set once the only match has been replaced, without the `g` flag.
+    done: bool,
+    /// The start of a char split by a match in byte mode, held until the rest of it is emitted.
+    partial_char: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl StreamingReplacer {
+    #[wasm_bindgen(constructor)]
+    pub fn new(pattern: &str, replacement: &str, flags: &str) -> Result<StreamingReplacer, Error> {
+        let flags = Flags::parse(flags);
+        let max_len = flags.parser().parse(pattern)?.properties().maximum_len();
+        let re = compile(pattern, &flags)?;
+        Ok(StreamingReplacer {
+            re,
+            flags,
+            replacement: replacement.to_owned(),
+            max_len,
+            buffer: String::new(),
+            pending: 0,
+            after_match: false,
+            done: false,
+            partial_char: Vec::new(),
+        })
+    }
+
+    /// Feeds the next chunk of input, returning the output that no later chunk can change.
+    pub fn push(&mut self, chunk: &str) -> String {
+        self.buffer.push_str(chunk);
+        self.replace(false)
+    }
+
+    /// Returns the rest of the output once the input has ended, and resets the replacer for a
+    /// new stream.
+    pub fn finish(&mut self) -> String {
+        let out = self.replace(true);
+        self.buffer.clear();
+        self.pending = 0;
+        self.after_match = false;
+        self.done = false;
+        out
+    }
+}
+
+impl StreamingReplacer {
+    /// Replaces the matches that can't change anymore and emits the input up to where the next
+    /// match could start.
+    fn replace(&mut self, at_end: bool) -> String {
+        let len = self.buffer.len();
+        // Matches starting at or before `safe` can't be extended by more input.
+        let safe = match self.max_len {
+            _ if at_end || self.done => Some(len),
+            Some(max_len) => len.checked_sub(max_len + LOOKAHEAD_MARGIN),
+            None => None,
+        };
+        let mut out = mem::take(&mut self.partial_char);
+        let mut emitted = self.pending;
+        let mut last_end = self.after_match.then_some(self.pending);
+        let flags = Flags {
+            global: true,
+            ..self.flags
+        };
+        if let Some(safe) = safe.filter(|_| !self.done) {
+            for caps in captures_iter(&self.re, &self.buffer, self.pending, &flags) {
+                let m = caps.get(0).unwrap();
+                if m.start() > safe {
+                    break;
+                }
+                if m.is_empty() && last_end == Some(m.start()) {
+                    continue;
+                }
+                out.extend_from_slice(&self.buffer.as_bytes()[emitted..m.start()]);
+                caps.expand(self.replacement.as_bytes(), &mut out);
+                emitted = m.end();
+                last_end = Some(m.end());
+                if !self.flags.global {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        let flush = match safe {
+            _ if self.done => len,
+            Some(safe) => emitted.max(floor_char_boundary(&self.buffer, safe)),
+            None => emitted,
+        };
+        out.extend_from_slice(&self.buffer.as_bytes()[emitted..flush]);
+        self.after_match = last_end == Some(flush);
+        let context = match flush {
+            0 => 0,
+            flush => floor_char_boundary(&self.buffer, flush - 1),
+        };
+        self.buffer.drain(..context);
+        self.pending = flush - context;
+        if !at_end {
+            self.partial_char = split_partial_char(&mut out);
+        }
+        into_string(out)
+    }
+}
+
+/// Finds the matches of a pattern in a text a batch at a time, for callers that yield to the
+/// event loop between batches on large inputs. `next` follows the JS iterator protocol, so in
+/// an `async` function the matcher can be iterated with
+/// `for await (const batch of { [Symbol.asyncIterator]: () => matcher })`, each `batch` being
+/// a result of [`crate::find::re_find`] with the next matches. Together the batches hold the
+/// same matches as a single `re_find`, with `match_num` and `adjacent_to_prev` carried across.
+#[wasm_bindgen]
+pub struct RegexMatcher {
+    re: Regex,
+    flags: Flags,
+    text: String,
+    batch_size: usize,
+    /// The search position of the captures iterator, see [`CapturesIter::position`].
+    search: (usize, Option<usize>),
+    /// The byte and UTF-16 offsets last converted, see [`Utf16Cursor::position`].
+    utf16: (usize, usize),
+    prev_end: Option<usize>,
+    /// How many matches have been reported.
+    match_num: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IteratorResultSer<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<MatchSer<'a>>,
+    pub done: bool,
+}
+
+#[wasm_bindgen]
+impl RegexMatcher {
+    /// A matcher over `text` that reports `batch_size` matches per call to `next`, 1 if `null`.
+    /// Without the `g` flag only the first match is reported, as with `re_find`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        text: String,
+        pattern: &str,
+        flags: &str,
+        batch_size: Option<usize>,
+    ) -> Result<RegexMatcher, Error> {
+        let flags = Flags::parse(flags);
+        let re = compile(pattern, &flags)?;
+        Ok(RegexMatcher {
+            re,
+            flags,
+            text,
+            batch_size: batch_size.unwrap_or(1).max(1),
+            search: (0, None),
+            utf16: (0, 0),
+            prev_end: None,
+            match_num: 0,
+        })
+    }
+
+    /// Returns `{ value, done }` with the next batch of matches as `value`, or `{ done: true }`
+    /// once there are no more.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> JsValue {
+        to_js(&self.next_batch())
+    }
+}
+
+impl RegexMatcher {
+    fn next_batch(&mut self) -> IteratorResultSer<'_> {
+        let limit = match self.flags.global {
+            true => self.batch_size,
+            false => 1usize.saturating_sub(self.match_num),
+        };
+        let mut captures =
+            CapturesIter::resume(&self.re, self.text.as_bytes(), self.search, &self.flags);
+        let mut cursor = Utf16Cursor::resume(&self.text, self.utf16);
+        let batch = collect_resumed(
+            &self.re,
+            0,
+            captures
+                .by_ref()
+                .take(limit)
+                .zip(self.match_num..)
+                .map(|(caps, n)| (n, caps)),
+            &mut cursor,
+            &mut self.prev_end,
+        );
+        self.search = captures.position();
+        self.utf16 = cursor.position();
+        self.match_num += batch.matches.len();
+        let done = batch.matches.is_empty();
+        IteratorResultSer {
+            value: (!done).then_some(batch),
+            done,
+        }
+    }
+}
+
+/// Splits off a UTF-8 sequence at the end of `out` that more output could still complete.
+fn split_partial_char(out: &mut Vec<u8>) -> Vec<u8> {
+    let start = (out.len().saturating_sub(3)..out.len()).find(|&i| {
+        let error = str::from_utf8(&out[i..]).err();
+        error.is_some_and(|e| e.valid_up_to() == 0 && e.error_len().is_none())
+    });
+    start.map_or_else(Vec::new, |start| out.split_off(start))
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::find::find;
+    use crate::replace::replace;
+
+    const PATTERNS: &[&str] = &[
+        "a",
+        "ab|b",
+        "a+",
+        r"\bab\b",
+        "^|$",
+        "(?m)^a|b$",
+        "a*",
+        "",
+        "é|😀",
+        r"\s+",
+        r"(?-u:\xC3)",
+        r"(a)(?:b(é))?",
+    ];
+
+    /// `text` cut into chunks at the char positions in `cuts`.
+    fn chunks(text: &str, cuts: &[usize]) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut cuts: Vec<_> = cuts.iter().map(|&c| c % (chars.len() + 1)).collect();
+        cuts.sort_unstable();
+        let mut last = 0;
+        let mut chunks = Vec::new();
+        for cut in cuts.into_iter().chain([chars.len()]) {
+            chunks.push(chars[last..cut].iter().collect());
+            last = cut;
+        }
+        chunks
+    }
+
+    proptest! {
+        #[test]
+        fn streaming_matches_replacing_all_at_once(
+            text in "[ab é😀\n]{0,40}",
+            cuts in prop::collection::vec(0usize..64, 0..8),
+            pattern in prop::sample::select(PATTERNS),
+            flags in prop::sample::select(&["g", "", "gm", "gi"][..]),
+        ) {
+            let mut replacer = StreamingReplacer::new(pattern, "<$1$0>", flags).unwrap();
+            let mut streamed = String::new();
+            for chunk in chunks(&text, &cuts) {
+                streamed += &replacer.push(&chunk);
+            }
+            streamed += &replacer.finish();
+            let flags = Flags::parse(flags);
+            let re = compile(pattern, &flags).unwrap();
+            prop_assert_eq!(streamed, replace(&re, &text, "<$1$0>", &flags));
+        }
+    }
+
+    #[test]
+    fn finish_resets_for_a_new_stream() {
+        let mut replacer = StreamingReplacer::new("^a", "x", "").unwrap();
+        assert_eq!(replacer.push("aa") + &replacer.finish(), "xa");
+        assert_eq!(
+            replacer.push("a") + &replacer.push("b") + &replacer.finish(),
+            "xb"
+        );
+    }
+
+    /// The matches of every batch of a [`RegexMatcher`] in order, as JSON.
+    fn batched(
+        text: &str,
+        pattern: &str,
+        flags: &str,
+        batch_size: usize,
+    ) -> Vec<serde_json::Value> {
+        let mut matcher = RegexMatcher::new(text.to_owned(), pattern, flags, Some(batch_size));
+        let matcher = matcher.as_mut().unwrap();
+        let mut all = Vec::new();
+        loop {
+            let batch = matcher.next_batch();
+            let Some(value) = batch.value else {
+                assert!(batch.done);
+                break;
+            };
+            assert!(!batch.done);
+            assert!((1..=batch_size).contains(&value.matches.len()));
+            all.extend(
+                value
+                    .matches
+                    .iter()
+                    .map(|m| serde_json::to_value(m).unwrap()),
+            );
+        }
+        assert!(matcher.next_batch().done);
+        all
+    }
+
+    /// The matches of [`find`] over the whole of `text`, as JSON.
+    fn bulk(text: &str, pattern: &str, flags: &str) -> Vec<serde_json::Value> {
+        let flags = Flags::parse(flags);
+        let re = compile(pattern, &flags).unwrap();
+        let matches = find(&re, text, &flags).matches;
+        matches
+            .iter()
+            .map(|m| serde_json::to_value(m).unwrap())
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn batches_concatenate_to_find(
+            text in "[ab é😀\n]{0,40}",
+            pattern in prop::sample::select(PATTERNS),
+            flags in prop::sample::select(&["g", "", "gm", "gi"][..]),
+            batch_size in 1usize..6,
+        ) {
+            let expected = bulk(&text, pattern, flags);
+            prop_assert_eq!(batched(&text, pattern, flags, batch_size), expected);
+        }
+    }
+
+    #[test]
+    fn empty_matches_at_batch_boundaries() {
+        for (text, pattern, flags) in [
+            ("baab😀", "a*", "g"),
+            ("ab😀", "", "g"),
+            ("b\nb\n\nb", "^|b", "gm"),
+        ] {
+            let expected = bulk(text, pattern, flags);
+            assert!(expected.len() > 2, "{pattern}");
+            for batch_size in [1, 2, 5] {
+                assert_eq!(
+                    batched(text, pattern, flags, batch_size),
+                    expected,
+                    "{pattern} in batches of {batch_size}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use std::borrow::Cow;
+use std::convert::Infallible;
+
+use regex::bytes::Regex;
+use regex_syntax::ast::{self, Ast, Visitor};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::analysis::{group_spans, parse_ast, SpanSer};
+use crate::find::{find, CapSer};
+use crate::flags::Flags;
+use crate::{compile, to_js, Error};
+
+/// A capture group of one match, with the groups nested in it in the pattern as `children`.
+/// The offsets and `content` are `None` if the group did not participate in the match.
+#[derive(Debug, Serialize)]
+pub struct GroupNodeSer<'a> {
+    pub group_num: usize,
+    pub name: Option<&'a str>,
+    pub content: Option<Cow<'a, str>>,
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+    pub start_utf16: Option<usize>,
+    pub end_utf16: Option<usize>,
+    pub children: Vec<GroupNodeSer<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchTreeSer<'a> {
+    pub match_num: usize,
+    /// Group 0, the whole match.
+    pub tree: GroupNodeSer<'a>,
+}
+
+/// Collects the innermost enclosing capture group of every capture group.
+struct GroupParents {
+    /// The capture groups being visited, innermost last.
+    open: Vec<usize>,
+    /// `parents[i]` is the parent of group `i`, 0 for top-level groups and for group 0 itself.
+    parents: Vec<usize>,
+}
+
+impl Visitor for GroupParents {
+    type Output = Vec<usize>;
+    type Err = Infallible;
+
+    fn finish(self) -> Result<Self::Output, Self::Err> {
+        Ok(self.parents)
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        if let Some(index) = capture_index(ast) {
+            let parent = self.open.last().copied().unwrap_or(0);
+            if self.parents.len() <= index {
+                self.parents.resize(index + 1, 0);
+            }
+            self.parents[index] = parent;
+            self.open.push(index);
+        }
+        Ok(())
+    }
+
+    fn visit_post(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        if capture_index(ast).is_some() {
+            self.open.pop();
+        }
+        Ok(())
+    }
+}
+
+fn capture_index(ast: &Ast) -> Option<usize> {
+    match ast {
+        Ast::Group(group) => group.capture_index().map(|index| index as usize),
+        _ => None,
+    }
+}
+
+/// Builds the node of `group_num` from the groups of one match, indexed by group number.
+fn node<'a>(
+    group_num: usize,
+    groups: &mut [Option<CapSer<'a>>],
+    names: &[Option<&'a str>],
+    children: &[Vec<usize>],
+) -> GroupNodeSer<'a> {
+    let group = groups[group_num].take();
+    GroupNodeSer {
+        group_num,
+        name: names[group_num],
+        children: children[group_num]
+            .iter()
+            .map(|&child| node(child, groups, names, children))
+            .collect(),
+        start: group.as_ref().map(|g| g.start),
+        end: group.as_ref().map(|g| g.end),
+        start_utf16: group.as_ref().and_then(|g| g.start_utf16),
+        end_utf16: group.as_ref().and_then(|g| g.end_utf16),
+        content: group.map(|g| g.content),
+    }
+}
+
+/// The innermost capture group enclosing each group of `pattern`, indexed by group number, with
+/// 0 for top-level groups and for group 0 itself.
+fn group_parents(pattern: &str, flags: &Flags) -> Result<Vec<usize>, Error> {
+    let visitor = GroupParents {
+        open: Vec::new(),
+        parents: Vec::new(),
+    };
+    let ast = parse_ast(pattern, flags)?;
+    Ok(ast::visit(&ast, visitor).unwrap_or_else(|e| match e {}))
+}
+
+/// The dotted group numbers from group 0 down to each group, indexed by group number.
+fn group_paths(parents: &[usize], captures_len: usize) -> Vec<String> {
+    let mut paths = vec![String::from("0")];
+    // Groups are numbered in the order they open, so a parent always comes first.
+    for group_num in 1..captures_len {
+        let parent = &paths[parents[group_num]];
+        paths.push(format!("{}.{}", parent, group_num));
+    }
+    paths
+}
+
+pub(crate) fn find_tree<'a>(
+    re: &'a Regex,
+    text: &'a str,
+    pattern: &str,
+    flags: &Flags,
+) -> Result<Vec<MatchTreeSer<'a>>, Error> {
+    let parents = group_parents(pattern, flags)?;
+    let mut children = vec![Vec::new(); re.captures_len()];
+    for (group_num, &parent) in parents.iter().enumerate().skip(1) {
+        children[parent].push(group_num);
+    }
+    let names: Vec<_> = re.capture_names().collect();
+    Ok(find(re, text, flags)
+        .matches
+        .into_iter()
+        .map(|m| {
+            let mut groups: Vec<_> = (0..re.captures_len()).map(|_| None).collect();
+            for g in m.groups {
+                let group_num = g.group_num;
+                groups[group_num] = Some(g);
+            }
+            MatchTreeSer {
+                match_num: m.match_num,
+                tree: node(0, &mut groups, &names, &children),
+            }
+        })
+        .collect())
+}
+
+/// Like `re_find`, but returns each match as `{ match_num, tree }`, where `tree` is group 0 and
+/// every group node lists the groups nested directly inside it in the pattern as `children`, in
+/// pattern order. Groups that did not participate in a match are still present, with `null`
+/// content and offsets, so every match has the same shape.
+#[wasm_bindgen]
+pub fn re_find_tree(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&find_tree(&re, text, pattern, &flags)?))
+}
+
+/// Like `re_find`, but gives each group a `path` of the group numbers from group 0 down to it
+/// through the groups it is nested in, such as `"0.2.3"` for group 3 inside group 2, for use as
+/// a stable key of the group in selection state. Top-level groups have paths like `"0.1"` and
+/// the whole match `"0"`.
+#[wasm_bindgen]
+pub fn re_find_with_paths(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    let paths = group_paths(&group_parents(pattern, &flags)?, re.captures_len());
+    let mut matches = find(&re, text, &flags);
+    matches.set_paths(&paths);
+    Ok(to_js(&matches))
+}
+
+/// A group of one match as a bar of a stacked chart, see [`re_find_intervals`].
+#[derive(Debug, Serialize)]
+pub struct IntervalSer {
+    pub group_num: usize,
+    /// How many groups the group is nested in, 0 for group 0.
+    pub depth: usize,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchIntervalsSer {
+    pub match_num: usize,
+    pub intervals: Vec<IntervalSer>,
+}
+
+pub(crate) fn find_intervals(
+    re: &Regex,
+    text: &str,
+    pattern: &str,
+    flags: &Flags,
+) -> Result<Vec<MatchIntervalsSer>, Error> {
+    let parents = group_parents(pattern, flags)?;
+    let mut depths = vec![0; re.captures_len()];
+    // Groups are numbered in the order they open, so a parent always comes first.
+    for group_num in 1..depths.len() {
+        depths[group_num] = depths[parents[group_num]] + 1;
+    }
+    Ok(find(re, text, flags)
+        .matches
+        .into_iter()
+        .map(|m| MatchIntervalsSer {
+            match_num: m.match_num,
+            intervals: m
+                .groups
+                .into_iter()
+                .map(|g| IntervalSer {
+                    group_num: g.group_num,
+                    depth: depths[g.group_num],
+                    start_utf16: g.start_utf16.unwrap(),
+                    end_utf16: g.end_utf16.unwrap(),
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+/// Returns each match as `{ match_num, intervals }` for drawing its groups as stacked bars, like
+/// a flame graph, with one `{ group_num, depth, start_utf16, end_utf16 }` per group that
+/// participated, in group order. `depth` is the number of groups the group is nested in in the
+/// pattern, 0 for the whole match and 1 for top-level groups, so bars of nested groups stack
+/// above those of their parents.
+#[wasm_bindgen]
+pub fn re_find_intervals(text: &str, pattern: &str, flags: &str) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&find_intervals(&re, text, pattern, &flags)?))
+}
+
+/// A group of one match with the span of the pattern that defines it.
+#[derive(Debug, Serialize)]
+pub struct ExplainedGroupSer<'a> {
+    #[serde(flatten)]
+    pub capture: CapSer<'a>,
+    /// The `(...)` of the group in the pattern, or the whole pattern for group 0.
+    pub source: SpanSer,
+}
+
+pub(crate) fn explain_match<'a>(
+    re: &'a Regex,
+    text: &'a str,
+    pattern: &str,
+    match_index: usize,
+    flags: &Flags,
+) -> Result<Option<Vec<ExplainedGroupSer<'a>>>, Error> {
+    let mut sources = vec![None; re.captures_len()];
+    sources[0] = Some(SpanSer {
+        start: 0,
+        end: pattern.len(),
+        start_utf16: 0,
+        end_utf16: pattern.encode_utf16().count(),
+    });
+    for group in group_spans(pattern, flags)? {
+        if let Some(index) = group.index {
+            sources[index as usize] = Some(group.span);
+        }
+    }
+    let Some(m) = find(re, text, flags).matches.into_iter().nth(match_index) else {
+        return Ok(None);
+    };
+    Ok(Some(
+        m.groups
+            .into_iter()
+            .map(|capture| ExplainedGroupSer {
+                // Every capture index comes from a group of the pattern.
+                source: sources[capture.group_num].clone().unwrap(),
+                capture,
+            })
+            .collect(),
+    ))
+}
+
+/// Returns the groups of the match at `match_index`, counting from 0, each as returned by
+/// `re_find` plus the `source` span `{ start, end, start_utf16, end_utf16 }` of the `(...)` that
+/// defines it in the pattern, so a highlighted group can be traced back to the pattern. The
+/// source of group 0 is the whole pattern. Groups that did not participate are left out, like in
+/// `re_find`, and `null` is returned if there are not that many matches. Without the `g` flag
+/// only index 0 exists.
+#[wasm_bindgen]
+pub fn re_explain_match(
+    text: &str,
+    pattern: &str,
+    match_index: usize,
+    flags: &str,
+) -> Result<JsValue, Error> {
+    let flags = Flags::parse(flags);
+    let re = compile(pattern, &flags)?;
+    Ok(to_js(&explain_match(
+        &re,
+        text,
+        pattern,
+        match_index,
+        &flags,
+    )?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The tree as `group_num(content children...)`, with `-` for groups that didn't take part.
+    fn outline(node: &GroupNodeSer) -> String {
+        let children: String = node.children.iter().map(outline).collect();
+        let content = node.content.as_deref().unwrap_or("-");
+        format!("{}({content}{children})", node.group_num)
+    }
+
+    fn trees(pattern: &str, text: &str) -> Vec<String> {
+        let flags = Flags::parse("g");
+        let re = compile(pattern, &flags).unwrap();
+        let trees = find_tree(&re, text, pattern, &flags).unwrap();
+        trees.iter().map(|m| outline(&m.tree)).collect()
+    }
+
+    #[test]
+    fn find_tree_nests_groups_as_in_the_pattern() {
+        assert_eq!(trees("((a)b(c))", "abc"), ["0(abc1(abc2(a)3(c)))"]);
+        assert_eq!(trees("(a)(?:(b)(c))", "abc"), ["0(abc1(a)2(b)3(c))"]);
+    }
+
+    #[test]
+    fn find_tree_keeps_groups_that_did_not_take_part() {
+        assert_eq!(
+            trees("(?P<x>(a)|(b))", "a b"),
+            ["0(a1(a2(a)3(-)))", "0(b1(b2(-)3(b)))"]
+        );
+        let flags = Flags::default();
+        let re = compile("(?P<x>(a)|(b))", &flags).unwrap();
+        let tree = &find_tree(&re, "b", "(?P<x>(a)|(b))", &flags).unwrap()[0].tree;
+        assert_eq!(tree.children[0].name, Some("x"));
+        assert_eq!(tree.children[0].children[0].start, None);
+    }
+
+    #[test]
+    fn explain_match_links_groups_to_their_source() {
+        let pattern = r"é(\d)(?:x)(?P<w>[a-z]+)?";
+        let flags = Flags::parse("g");
+        let re = compile(pattern, &flags).unwrap();
+        let explained = explain_match(&re, "é1x é2xab", pattern, 1, &flags)
+            .unwrap()
+            .unwrap();
+        let sources: Vec<_> = explained
+            .iter()
+            .map(|g| {
+                (
+                    g.capture.group_num,
+                    &*g.capture.content,
+                    g.source.start_utf16,
+                    g.source.end_utf16,
+                )
+            })
+            .collect();
+        assert_eq!(
+            sources,
+            [(0, "é2xab", 0, 24), (1, "2", 1, 5), (2, "ab", 10, 23)]
+        );
+        // The first match has no `w`, and there is no third match.
+        let first = explain_match(&re, "é1x é2xab", pattern, 0, &flags)
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.len(), 2);
+        assert!(explain_match(&re, "é1x é2xab", pattern, 2, &flags)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn group_paths_follow_nesting() {
+        let pattern = "(a)((b)(?:(c)))(d)";
+        let flags = Flags::default();
+        let re = compile(pattern, &flags).unwrap();
+        let paths = group_paths(&group_parents(pattern, &flags).unwrap(), re.captures_len());
+        assert_eq!(paths, ["0", "0.1", "0.2", "0.2.3", "0.2.4", "0.5"]);
+
+        let mut matches = find(&re, "abcd", &flags);
+        matches.set_paths(&paths);
+        let deepest = matches.matches[0]
+            .groups
+            .iter()
+            .find(|g| g.group_num == 3)
+            .unwrap();
+        assert_eq!(deepest.path.as_deref(), Some("0.2.3"));
+        assert_eq!(deepest.path.as_ref().unwrap().split('.').count(), 3);
+    }
+
+    #[test]
+    fn find_intervals_deepen_with_nesting_and_skip_absent_groups() {
+        let pattern = "((a)(?:(b)|(c)))";
+        let flags = Flags::parse("g");
+        let re = compile(pattern, &flags).unwrap();
+        let matches = find_intervals(&re, "ab 😀ac", pattern, &flags).unwrap();
+        let intervals: Vec<Vec<_>> = matches
+            .iter()
+            .map(|m| {
+                m.intervals
+                    .iter()
+                    .map(|i| (i.group_num, i.depth, i.start_utf16, i.end_utf16))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            intervals,
+            [
+                vec![(0, 0, 0, 2), (1, 1, 0, 2), (2, 2, 0, 1), (3, 2, 1, 2)],
+                vec![(0, 0, 5, 7), (1, 1, 5, 7), (2, 2, 5, 6), (4, 2, 6, 7)],
+            ]
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use wasm_bindgen::prelude::*;
+
+/// Converts a UTF-8 byte offset into `text` to a UTF-16 code unit offset, which is what JS
+/// string indexing uses. Offsets inside a multi-byte char are floored to the char start and
+/// offsets past the end are clamped to the length of `text`.
+pub fn utf16_index_bytes(text: &str, byte_idx: usize) -> usize {
+    text[..floor_char_boundary(text, byte_idx)]
+        .encode_utf16()
+        .count()
+}
+
+/// Converts a UTF-16 code unit offset into `text` back to a UTF-8 byte offset, the inverse of
+/// [`utf16_index_bytes`] on char boundaries. Offsets between the two halves of a surrogate pair
+/// are floored to the char start and offsets past the end are clamped to the length of `text`.
+pub fn byte_index_utf16(text: &str, utf16_idx: usize) -> usize {
+    let mut utf16 = 0;
+    for (i, c) in text.char_indices() {
+        utf16 += c.len_utf16();
+        if utf16 > utf16_idx {
+            return i;
+        }
+    }
+    text.len()
+}
+
+/// The UTF-16 length of `text[start..end]`, with both offsets converted like
+/// [`utf16_index_bytes`].
+pub(crate) fn utf16_len(text: &str, start: usize, end: usize) -> usize {
+    let start = floor_char_boundary(text, start);
+    let end = floor_char_boundary(text, end).max(start);
+    text[start..end].encode_utf16().count()
+}
+
+pub(crate) fn floor_char_boundary(text: &str, byte_idx: usize) -> usize {
+    let mut idx = byte_idx.min(text.len());
+    while !text.is_char_boundary(idx) {
+        idx -= 1;
+    }
+    idx
+}
+
+pub(crate) fn ceil_char_boundary(text: &str, byte_idx: usize) -> usize {
+    let mut idx = byte_idx.min(text.len());
+    while !text.is_char_boundary(idx) {
+        idx += 1;
+    }
+    idx
+}
+
+/// Converts byte offsets to UTF-16 offsets like [`utf16_index_bytes`], but only counts the
+/// distance from the previously converted offset. Converting the offsets of successive matches
+/// is linear in the length of `text` instead of quadratic.
+pub struct Utf16Cursor<'a> {
+    bytes: &'a [u8],
+    byte_idx: usize,
+    utf16_idx: usize,
+}
+
+impl<'a> Utf16Cursor<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Utf16Cursor::from_bytes(text.as_bytes())
+    }
+
+    /// A cursor over bytes that may not be valid UTF-8. Offsets are counted in the string JS
+    /// gets from decoding `bytes` with a `TextDecoder`, where each invalid sequence becomes one
+    /// U+FFFD.
+    pub fn from_bytes(bytes: &'a [u8]) -> Self {
+        Utf16Cursor {
+            bytes,
+            byte_idx: 0,
+            utf16_idx: 0,
+        }
+    }
+
+    /// A cursor over `text` that last converted the byte offset `byte_idx` to `utf16_idx`, as
+    /// returned by [`Utf16Cursor::position`], to carry on counting across calls.
+    pub fn resume(text: &'a str, (byte_idx, utf16_idx): (usize, usize)) -> Self {
+        Utf16Cursor {
+            bytes: text.as_bytes(),
+            byte_idx,
+            utf16_idx,
+        }
+    }
+
+    /// The byte offset last converted and its UTF-16 offset.
+    pub fn position(&self) -> (usize, usize) {
+        (self.byte_idx, self.utf16_idx)
+    }
+
+    pub fn utf16(&mut self, byte_idx: usize) -> usize {
+        let idx = floor_utf8_boundary(self.bytes, byte_idx);
+        if idx >= self.byte_idx {
+            self.utf16_idx += lossy_utf16_len(&self.bytes[self.byte_idx..idx]);
+        } else {
+            self.utf16_idx -= lossy_utf16_len(&self.bytes[idx..self.byte_idx]);
+        }
+        self.byte_idx = idx;
+        self.utf16_idx
+    }
+}
+
+/// Converts byte offsets into `text` to code point offsets, counting from the previously
+/// converted offset like [`Utf16Cursor`]. Offsets inside a char are floored to its start.
+pub struct Utf32Cursor<'a> {
+    text: &'a str,
+    byte_idx: usize,
+    utf32_idx: usize,
+}
+
+impl<'a> Utf32Cursor<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Utf32Cursor {
+            text,
+            byte_idx: 0,
+            utf32_idx: 0,
+        }
+    }
+
+    pub fn utf32(&mut self, byte_idx: usize) -> usize {
+        let idx = floor_char_boundary(self.text, byte_idx);
+        if idx >= self.byte_idx {
+            self.utf32_idx += self.text[self.byte_idx..idx].chars().count();
+        } else {
+            self.utf32_idx -= self.text[idx..self.byte_idx].chars().count();
+        }
+        self.byte_idx = idx;
+        self.utf32_idx
+    }
+}
+
+/// [`floor_char_boundary`] for bytes that may not be valid UTF-8, where each invalid sequence
+/// replaced by [`String::from_utf8_lossy`] counts as one char.
+fn floor_utf8_boundary(bytes: &[u8], byte_idx: usize) -> usize {
+    let idx = byte_idx.min(bytes.len());
+    for back in 1..=3.min(idx) {
+        let start = idx - back;
+        if (bytes[start] as i8) >= -0x40 {
+            let window = &bytes[start..bytes.len().min(start + 4)];
+            let chunk = window.utf8_chunks().next().unwrap();
+            let len = chunk
+                .valid()
+                .chars()
+                .next()
+                .map_or(chunk.invalid().len(), char::len_utf8);
+            return if len > back { start } else { idx };
+        }
+    }
+    idx
+}
+
+/// The UTF-16 length of `String::from_utf8_lossy(bytes)`.
+fn lossy_utf16_len(bytes: &[u8]) -> usize {
+    bytes
+        .utf8_chunks()
+        .map(|chunk| {
+            let replacement = usize::from(!chunk.invalid().is_empty());
+            chunk.valid().encode_utf16().count() + replacement
+        })
+        .sum()
+}
+
+/// Converts a byte offset into `text` to a UTF-16 offset, see [`utf16_index_bytes`].
+#[wasm_bindgen]
+pub fn byte_to_utf16_offset(text: &str, byte_offset: usize) -> usize {
+    utf16_index_bytes(text, byte_offset)
+}
+
+/// Converts a UTF-16 offset into `text`, such as a JS string index, to the byte offset taken by
+/// functions like `re_find_in_range`, see [`byte_index_utf16`].
+#[wasm_bindgen]
+pub fn utf16_to_byte_offset(text: &str, utf16_offset: usize) -> usize {
+    byte_index_utf16(text, utf16_offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Strings mixing ASCII, 2 and 3 byte chars and chars outside the BMP, which take a
+    /// surrogate pair in UTF-16.
+    fn text() -> impl Strategy<Value = String> {
+        proptest::collection::vec(
+            prop_oneof![
+                proptest::char::range('\0', '\x7f'),
+                proptest::char::range('\u{80}', '\u{7ff}'),
+                proptest::char::range('\u{800}', '\u{ffff}'),
+                proptest::char::range('\u{10000}', '\u{10ffff}'),
+            ],
+            0..20,
+        )
+        .prop_map(|chars| chars.into_iter().collect())
+    }
+
+    proptest! {
+        #[test]
+        fn byte_offsets_round_trip_through_utf16(text in text(), byte_idx in 0usize..100) {
+            let back = byte_index_utf16(&text, utf16_index_bytes(&text, byte_idx));
+            // Char boundaries round trip, offsets inside a char floor to its start and offsets
+            // past the end clamp to the end.
+            prop_assert_eq!(back, floor_char_boundary(&text, byte_idx));
+            if text.is_char_boundary(byte_idx) {
+                prop_assert_eq!(back, byte_idx);
+            }
+        }
+
+        #[test]
+        fn utf16_offsets_round_trip_through_bytes(text in text(), utf16_idx in 0usize..60) {
+            let byte_idx = byte_index_utf16(&text, utf16_idx);
+            prop_assert!(text.is_char_boundary(byte_idx));
+            let back = utf16_index_bytes(&text, byte_idx);
+            let len = text.encode_utf16().count();
+            // Offsets between the halves of a surrogate pair floor to the start of the char.
+            let units: Vec<u16> = text.encode_utf16().collect();
+            let mid_pair = utf16_idx < len && (0xdc00..0xe000).contains(&units[utf16_idx]);
+            prop_assert_eq!(back, if mid_pair { utf16_idx - 1 } else { utf16_idx.min(len) });
+        }
+
+        #[test]
+        fn cursors_agree_with_direct_conversion(
+            text in text(),
+            offsets in proptest::collection::vec(0usize..100, 0..10),
+        ) {
+            let mut utf16 = Utf16Cursor::new(&text);
+            let mut utf32 = Utf32Cursor::new(&text);
+            for byte_idx in offsets {
+                prop_assert_eq!(utf16.utf16(byte_idx), utf16_index_bytes(&text, byte_idx));
+                let chars = text[..floor_char_boundary(&text, byte_idx)].chars().count();
+                prop_assert_eq!(utf32.utf32(byte_idx), chars);
+            }
+        }
+    }
+
+    #[test]
+    fn utf32_cursor_counts_code_points_in_any_order() {
+        let text = "a😀éb";
+        let mut cursor = Utf32Cursor::new(text);
+        assert_eq!(cursor.utf32(7), 3);
+        assert_eq!(cursor.utf32(1), 1);
+        // Inside the emoji floors to its start, past the end clamps to the end.
+        assert_eq!(cursor.utf32(3), 1);
+        assert_eq!(cursor.utf32(100), 4);
+        assert_eq!(cursor.utf32(0), 0);
+    }
+}